@@ -25,3 +25,35 @@ pub enum MatchActions {
     SpawnOrLaunchBall,
     GrabTheBall
 }
+
+#[derive(Actionlike, PartialEq, Eq, Clone, Copy, Hash, Debug)]
+pub enum EditorActions {
+    CursorUp,
+    CursorDown,
+    CursorLeft,
+    CursorRight,
+
+    CycleType,
+    CycleBehaviour,
+    CycleTrigger,
+    CycleTriggerGroup,
+    ClearCell,
+
+    AddBox,
+    AddForceField,
+    RotateObstacle,
+    GrowObstacleWidth,
+    ShrinkObstacleWidth,
+    GrowObstacleDepth,
+    ShrinkObstacleDepth,
+    RemoveObstacle,
+
+    Save,
+    PlayTest,
+    BackToEditor,
+    Generate,
+    CycleDifficulty,
+    PreviousLevel,
+    NextLevel,
+    Exit,
+}
@@ -0,0 +1,138 @@
+use bevy::hierarchy::Parent;
+use bevy::math::Vec3;
+use bevy::prelude::{Added, Commands, Component, Entity, Name, Query, Transform, TransformBundle, Visibility};
+use bevy::render::primitives::Aabb;
+use bevy_rapier3d::dynamics::CoefficientCombineRule;
+use bevy_rapier3d::prelude::{Collider, Friction, Restitution};
+
+use crate::arena::Arena;
+use crate::config::MAX_RESTITUTION;
+use crate::physics::{Collidable, CollidableKind};
+
+
+/*
+    Background scenes can carry their own collision. Meshes, or the nodes holding them, named
+
+        COL_Box_<anything>            walls the ball bounces off
+        COL_DeathTrigger_<anything>   balls touching them are lost
+
+    get a cuboid collider matching their bounding box and are not rendered. The background is
+    spawned several times to scroll, only the copy marked SceneColliderSource gives colliders.
+    They are placed where that copy was spawned and do not scroll with it.
+ */
+
+#[derive(Component)]
+pub struct SceneColliderSource {
+    // Transform the scene was spawned with
+    pub origin: Transform,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SceneColliderKind {
+    Box,
+    DeathTrigger,
+}
+
+pub fn scene_collider_kind(name: &str) -> Option<SceneColliderKind> {
+    if name.starts_with("COL_Box_") {
+        Some(SceneColliderKind::Box)
+    } else if name.starts_with("COL_DeathTrigger_") {
+        Some(SceneColliderKind::DeathTrigger)
+    } else {
+        None
+    }
+}
+
+
+// Transform of the entity as if its source scene were still at its origin, None for entities
+// of other scenes
+fn source_transform(
+    entity: Entity,
+    hierarchy: &Query<(&Transform, Option<&Parent>, Option<&SceneColliderSource>)>,
+) -> Option<Transform> {
+    let mut local = Transform::IDENTITY;
+    let mut current = entity;
+
+    loop {
+        let (trans, parent, source) = hierarchy.get(current).ok()?;
+
+        if let Some(source) = source {
+            return Some(source.origin.mul_transform(local));
+        }
+
+        local = trans.mul_transform(local);
+        current = parent?.get();
+    }
+}
+
+
+pub(super) fn arena_spawn_scene_colliders(
+    mut commands: Commands,
+    mut meshes: Query<(Entity, &Aabb, Option<&Name>, Option<&Parent>, &mut Visibility), Added<Aabb>>,
+    names: Query<&Name>,
+    hierarchy: Query<(&Transform, Option<&Parent>, Option<&SceneColliderSource>)>,
+) {
+    for (entity, aabb, name, parent, mut visibility) in &mut meshes {
+        // glTF names the mesh entity after the mesh and its parent after the node
+        let parent_name = parent.and_then(|p| names.get(p.get()).ok());
+
+        let kind = name
+            .and_then(|n| scene_collider_kind(n.as_str()))
+            .or_else(|| parent_name.and_then(|n| scene_collider_kind(n.as_str())));
+
+        let Some(kind) = kind else { continue; };
+
+        visibility.is_visible = false;
+
+        let Some(world) = source_transform(entity, &hierarchy) else { continue; };
+
+        let half = Vec3::from(aabb.half_extents) * world.scale;
+        let collider = Collider::cuboid(half.x, half.y, half.z);
+        let transform = TransformBundle::from(
+            Transform::from_translation(world.transform_point(Vec3::from(aabb.center)))
+                .with_rotation(world.rotation)
+        );
+
+        match kind {
+            SceneColliderKind::Box => {
+                commands
+                    .spawn(collider)
+                    .insert(transform)
+                    .insert(Restitution {
+                        coefficient: MAX_RESTITUTION,
+                        combine_rule: CoefficientCombineRule::Max,
+                    })
+                    .insert(Friction::coefficient(0.0))
+                    .insert(Collidable {
+                        kind: CollidableKind::Wall,
+                    })
+                    .insert(Arena);
+            }
+
+            SceneColliderKind::DeathTrigger => {
+                commands
+                    .spawn(collider)
+                    .insert(transform)
+                    .insert(Collidable {
+                        kind: CollidableKind::DeathTrigger,
+                    })
+                    .insert(Arena);
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_collider_names() {
+        assert_eq!(scene_collider_kind("COL_Box_FactoryWall"), Some(SceneColliderKind::Box));
+        assert_eq!(scene_collider_kind("COL_Box_FactoryWall.001"), Some(SceneColliderKind::Box));
+        assert_eq!(scene_collider_kind("COL_DeathTrigger_Pit"), Some(SceneColliderKind::DeathTrigger));
+        assert_eq!(scene_collider_kind("COL_Sphere_Pit"), None);
+        assert_eq!(scene_collider_kind("ValleyMesh"), None);
+    }
+}
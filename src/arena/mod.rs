@@ -1,16 +1,20 @@
-use std::f32::consts::PI;
+mod colliders;
+
+use std::f32::consts::{PI, TAU};
 use bevy::hierarchy::{BuildChildren, Parent};
 use bevy::log::info;
 use bevy::math::{Quat, Vec2, Vec3};
 use bevy::pbr::{NotShadowReceiver, StandardMaterial};
-use bevy::prelude::{Component, App, AssetServer, Commands, default, Plugin, Res, SystemSet, TransformBundle, Transform, Query, With, Time, IntoSystemDescriptor, Entity, DespawnRecursiveExt, Assets, ResMut, MaterialPlugin, MaterialMeshBundle, shape, Mesh, Color, AlphaMode, SceneBundle, Handle, Without, Name};
+use bevy::prelude::{Component, App, AssetServer, Commands, default, Plugin, Res, SystemSet, TransformBundle, Transform, Query, With, Time, IntoSystemDescriptor, Entity, DespawnRecursiveExt, Assets, ResMut, MaterialPlugin, MaterialMeshBundle, shape, Mesh, Color, AlphaMode, SceneBundle, Handle, Without, Name, EventReader};
 use bevy_rapier3d::dynamics::CoefficientCombineRule;
 use bevy_rapier3d::na::inf;
 use bevy_rapier3d::prelude::{ActiveEvents, Collider, CollisionEvent, CollisionGroups, Friction, Restitution, RigidBody, Sensor};
 
+use crate::arena::colliders::{arena_spawn_scene_colliders, SceneColliderSource};
+use crate::block::trigger::{TriggerGroup, TriggerStates};
 use crate::config::{ARENA_HEIGHT, ARENA_HEIGHT_H, ARENA_WIDTH, ARENA_WIDTH_H, BACKGROUND_LENGTH, BACKGROUND_SPEED, COLLIDER_GROUP_BALL, COLLIDER_GROUP_BLOCK, COLLIDER_GROUP_DEATH, MAX_RESTITUTION};
 use crate::labels::SystemLabels;
-use crate::level::{LevelObstacle, Levels};
+use crate::level::{LevelDefinition, LevelObstacle, LevelReloaded, Levels};
 use crate::materials::arena::ArenaMaterial;
 use crate::materials::background::BackgroundMaterial;
 use crate::materials::CustomMaterialApplied;
@@ -25,6 +29,65 @@ pub struct Arena;
 pub struct ForceField;
 
 
+// Kinematic obstacle moving through its waypoints in a loop
+#[derive(Component)]
+pub struct ObstaclePath {
+    waypoints: Vec<Vec3>,
+    speed: f32,
+    // Waypoint the obstacle is heading to
+    next: usize,
+    group: Option<TriggerGroup>,
+}
+
+// Kinematic obstacle rotating around a pivot
+#[derive(Component)]
+pub struct ObstacleRotation {
+    pivot: Vec3,
+    offset: Vec3,
+    angular_speed: f32,
+    angle: f32,
+    group: Option<TriggerGroup>,
+}
+
+impl ObstaclePath {
+    // Moves the position by the distance, passing as many waypoints as needed
+    fn advance(&mut self, mut position: Vec3, distance: f32) -> Vec3 {
+        let count = self.waypoints.len();
+        let length: f32 = (0..count)
+            .map(|i| self.waypoints[i].distance(self.waypoints[(i + 1) % count]))
+            .sum();
+
+        if length <= 0.0 {
+            return position;
+        }
+
+        // Whole laps end where they started
+        let mut distance = distance % length;
+
+        for _ in 0..=count {
+            let target = self.waypoints[self.next];
+            let to_target = target.distance(position);
+
+            if to_target > distance {
+                return position + (target - position) / to_target * distance;
+            }
+
+            position = target;
+            distance -= to_target;
+            self.next = (self.next + 1) % count;
+        }
+
+        position
+    }
+}
+
+fn obstacle_running(group: Option<TriggerGroup>, trigger_states: &TriggerStates) -> bool {
+    match group {
+        None => true,
+        Some(group) => trigger_states.is_started(group),
+    }
+}
+
 #[derive(Component)]
 pub struct Scrollable {
     speed: f32,
@@ -47,6 +110,9 @@ impl Plugin for ArenaPlugin {
                 SystemSet::on_update(GameState::InMatch)
                     .with_system(arena_scroll.label(SystemLabels::UpdateWorld))
                     .with_system(arena_update_force_field_material.label(SystemLabels::UpdateWorld))
+                    .with_system(arena_move_obstacles.label(SystemLabels::UpdateWorld))
+                    .with_system(arena_spawn_scene_colliders)
+                    .with_system(arena_respawn_on_reload.after(SystemLabels::LevelReload))
             )
 
 
@@ -69,6 +135,39 @@ fn arena_spawn(
 ) {
     let level = levels.get_current_level().unwrap();
 
+    arena_spawn_level(&mut commands, &asset_server, level, &mut force_field_mat, &mut meshes);
+}
+
+fn arena_respawn_on_reload(
+    mut commands: Commands,
+    mut events: EventReader<LevelReloaded>,
+    asset_server: Res<AssetServer>,
+    levels: Res<Levels>,
+    mut force_field_mat: ResMut<Assets<ForceFieldMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    arena_parts: Query<Entity, With<Arena>>,
+) {
+    if events.iter().count() == 0 {
+        return;
+    }
+
+    for part in &arena_parts {
+        commands.entity(part)
+            .despawn_recursive();
+    }
+
+    let level = levels.get_current_level().unwrap();
+
+    arena_spawn_level(&mut commands, &asset_server, level, &mut force_field_mat, &mut meshes);
+}
+
+fn arena_spawn_level(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    level: &LevelDefinition,
+    force_field_mat: &mut Assets<ForceFieldMaterial>,
+    meshes: &mut Assets<Mesh>,
+) {
     commands
         .spawn(SceneBundle {
             scene: asset_server.load(level.background_asset.clone()),
@@ -81,6 +180,10 @@ fn arena_spawn(
         .insert(Scrollable {
             speed: level.background_scroll_velocity.clone(),
         })
+        // The copies behind it repeat its colliders
+        .insert(SceneColliderSource {
+            origin: Transform::from_xyz(0.0, -4.0, 0.0),
+        })
     ;
 
     commands
@@ -170,6 +273,7 @@ fn arena_spawn(
         })
 
         .insert(ForceField)
+        .insert(Arena)
         .with_children(|parent| {
             parent
                 .spawn(RigidBody::Fixed)
@@ -267,6 +371,70 @@ fn arena_spawn(
                     );
             }
 
+            LevelObstacle::PathBox(waypoints, w, h, speed, group) => {
+                commands
+                    .spawn(RigidBody::KinematicPositionBased)
+                    .insert(Collider::cuboid(w / 2.0, 100.0, h / 2.0))
+                    .insert(TransformBundle::from(Transform::from_translation(waypoints[0])))
+                    .insert(Restitution {
+                        coefficient: MAX_RESTITUTION,
+                        combine_rule: CoefficientCombineRule::Max,
+                    })
+                    .insert(Friction::coefficient(0.0))
+                    .insert(Collidable {
+                        kind: CollidableKind::Wall,
+                    })
+                    .insert(ObstaclePath {
+                        waypoints: waypoints.clone(),
+                        speed: *speed,
+                        next: 1,
+                        group: *group,
+                    })
+                    .insert(
+                        Arena
+                    );
+            }
+
+            LevelObstacle::RotatingBox(pivot, offset, w, h, speed, group) => {
+                commands
+                    .spawn(RigidBody::KinematicPositionBased)
+                    .insert(Collider::cuboid(w / 2.0, 100.0, h / 2.0))
+                    .insert(TransformBundle::from(Transform::from_translation(*pivot + *offset)))
+                    .insert(Restitution {
+                        coefficient: MAX_RESTITUTION,
+                        combine_rule: CoefficientCombineRule::Max,
+                    })
+                    .insert(Friction::coefficient(0.0))
+                    .insert(Collidable {
+                        kind: CollidableKind::Wall,
+                    })
+                    .insert(ObstacleRotation {
+                        pivot: *pivot,
+                        offset: *offset,
+                        angular_speed: *speed,
+                        angle: 0.0,
+                        group: *group,
+                    })
+                    .insert(
+                        Arena
+                    );
+            }
+
+            LevelObstacle::Lift(pos, w, h, a, b) => {
+                commands
+                    .spawn(RigidBody::Fixed)
+                    .insert(Collider::cuboid(w / 2.0, 100.0, h / 2.0))
+                    .insert(TransformBundle::from(Transform::from_translation(pos.clone())))
+                    .insert(Collidable {
+                        kind: CollidableKind::Lift(*a, *b),
+                    })
+                    .insert(CollisionGroups::new(COLLIDER_GROUP_BLOCK, COLLIDER_GROUP_BALL))
+                    .insert(ActiveEvents::COLLISION_EVENTS)
+                    .insert(Sensor)
+                    .insert(Arena)
+                ;
+            }
+
             LevelObstacle::DirectionalDeathTrigger(origin, normal, size) => {
                 let angle = Vec3::Z.angle_between(*normal);
                 let rot = Quat::from_rotation_y(angle);
@@ -282,6 +450,7 @@ fn arena_spawn(
                     .insert(CollisionGroups::new(COLLIDER_GROUP_DEATH, COLLIDER_GROUP_BLOCK))
                     .insert(ActiveEvents::COLLISION_EVENTS)
                     .insert(Sensor)
+                    .insert(Arena)
 
                 ;
             }
@@ -338,6 +507,37 @@ fn arena_scroll(
     }
 }
 
+// Kinematic bodies are moved through their transform, rapier derives their velocity for the ball
+fn arena_move_obstacles(
+    time: Res<Time>,
+    trigger_states: Res<TriggerStates>,
+    mut paths: Query<(&mut Transform, &mut ObstaclePath), Without<ObstacleRotation>>,
+    mut rotations: Query<(&mut Transform, &mut ObstacleRotation), Without<ObstaclePath>>,
+) {
+    let dt = time.delta_seconds();
+
+    for (mut trans, mut path) in &mut paths {
+        if !obstacle_running(path.group, &trigger_states) {
+            continue;
+        }
+
+        let distance = path.speed * dt;
+        trans.translation = path.advance(trans.translation, distance);
+    }
+
+    for (mut trans, mut rotation) in &mut rotations {
+        if !obstacle_running(rotation.group, &trigger_states) {
+            continue;
+        }
+
+        rotation.angle = (rotation.angle + rotation.angular_speed * dt) % TAU;
+
+        let q = Quat::from_rotation_y(rotation.angle);
+        trans.translation = rotation.pivot + q * rotation.offset;
+        trans.rotation = q;
+    }
+}
+
 fn arena_update_force_field_material(
     mut materials: ResMut<Assets<ForceFieldMaterial>>,
     time: Res<Time>,
@@ -377,4 +577,30 @@ fn arena_handle_collisions(
             }
         }
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_its_path_in_a_loop() {
+        let mut path = ObstaclePath {
+            waypoints: vec![Vec3::ZERO, Vec3::new(10.0, 0.0, 0.0), Vec3::new(10.0, 0.0, 10.0)],
+            speed: 1.0,
+            next: 1,
+            group: None,
+        };
+
+        let p = path.advance(Vec3::ZERO, 15.0);
+        assert!(p.distance(Vec3::new(10.0, 0.0, 5.0)) < 0.001);
+        assert_eq!(path.next, 2);
+
+        // Back along the diagonal and a full lap on top
+        let lap = 20.0 + 200.0_f32.sqrt();
+        let p = path.advance(p, 5.0 + 200.0_f32.sqrt() / 2.0 + lap);
+        assert!(p.distance(Vec3::new(5.0, 0.0, 5.0)) < 0.001, "{:?}", p);
+        assert_eq!(path.next, 0);
+    }
+}
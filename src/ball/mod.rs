@@ -11,6 +11,7 @@ use crate::config::{BALL_RADIUS, COLLIDER_GROUP_BALL, COLLIDER_GROUP_BLOCK, COLL
 use crate::events::MatchEvent;
 use crate::labels::SystemLabels;
 use crate::level::RequestTag;
+use crate::level::layers::{layer_elevation, nearest_layer};
 use crate::physics::{Collidable, CollidableKind, COLLISION_EVENT_HANDLING, CollisionInfo, CollisionTag};
 use crate::ship::ShipState;
 
@@ -142,6 +143,8 @@ fn ball_update_inactive(
 {
     for (mut trans, mut velo, mut impulse) in &mut query {
         trans.translation = ship_state.ship_position.clone() + Vec3::new(0.0, 0.0, -PADDLE_THICKNESS * 0.7 - BALL_RADIUS);
+        // Launched from the layer closest to the ship
+        trans.translation.y = layer_elevation(nearest_layer(ship_state.ship_position.y));
         // velo.linvel = Vec3::ZERO;
         // impulse.impulse = Vec3::ZERO;
     }
@@ -229,11 +232,11 @@ fn ball_correct_too_low_z(mut query: Query<&mut Velocity, With<ActiveBall>>) {
 fn ball_handle_collisions(
     mut commands: Commands,
     ship_state: Res<ShipState>,
-    mut balls: Query<(Entity, &mut ExternalImpulse, &mut Velocity), (With<ActiveBall>, With<CollisionTag>)>,
+    mut balls: Query<(Entity, &mut ExternalImpulse, &mut Velocity, &mut Transform), (With<ActiveBall>, With<CollisionTag>)>,
     mut events: EventWriter<MatchEvent>,
     collisions: Res<CollisionInfo>,
 ) {
-    for (ball, mut ext_imp, mut velo) in &mut balls {
+    for (ball, mut ext_imp, mut velo, mut trans) in &mut balls {
         let mut correct_ball_trans = false;
 
         if let Some(collision) = collisions.collisions.get(&ball) {
@@ -245,6 +248,9 @@ fn ball_handle_collisions(
                             ship_state.ship_rotation, PADDLE_BOUNCE_IMPULSE,
                         );*/
 
+                        // The ship reaches every layer and sends the ball back on its own
+                        trans.translation.y = layer_elevation(nearest_layer(ship_state.ship_position.y));
+
                         commands.entity(ball)
                             .remove::<CollisionTag>();
                         events.send(MatchEvent::BounceOffPaddle);
@@ -265,6 +271,16 @@ fn ball_handle_collisions(
                         correct_ball_trans = true;
                     }
 
+                    CollidableKind::Lift(a, b) => {
+                        let layer = nearest_layer(trans.translation.y);
+
+                        if layer == a {
+                            trans.translation.y = layer_elevation(b);
+                        } else if layer == b {
+                            trans.translation.y = layer_elevation(a);
+                        }
+                    }
+
                     _ => {}
                 }
             }
@@ -0,0 +1,121 @@
+use bevy::asset::Assets;
+use bevy::hierarchy::BuildChildren;
+use bevy::pbr::{AlphaMode, PbrBundle, StandardMaterial};
+use bevy::prelude::{Color, Commands, Component, Entity, Mesh, Query, ResMut, shape, Transform, Vec3, With, Without};
+use bevy::utils::default;
+use bevy_rapier3d::prelude::ExternalForce;
+
+use crate::ball::Ball;
+
+
+/*
+    Field blocks push balls within their radius away, or pull them in with a negative strength.
+    The force is strongest at the block and fades towards the radius:
+
+        force = strength * (1 - distance / radius) ^ falloff
+
+    A falloff of 0 keeps the full strength up to the radius, 1 fades linearly. The forces of
+    overlapping fields add up.
+ */
+#[derive(Component)]
+pub struct BlockField {
+    pub radius: f32,
+    pub strength: f32,
+    pub falloff: f32,
+}
+
+// Marks fields that already show their radius
+#[derive(Component)]
+pub(super) struct BlockFieldDebug;
+
+
+// Force on a ball at the offset from the field's center
+pub fn field_force(field: &BlockField, offset: Vec3) -> Vec3 {
+    let distance = offset.length();
+
+    if distance >= field.radius || distance == 0.0 {
+        return Vec3::ZERO;
+    }
+
+    let strength = field.strength * (1.0 - distance / field.radius).powf(field.falloff);
+    offset / distance * strength
+}
+
+
+pub(super) fn block_apply_fields(
+    fields: Query<(&Transform, &BlockField)>,
+    mut balls: Query<(&Transform, &mut ExternalForce), With<Ball>>,
+) {
+    for (ball_trans, mut ball_force) in &mut balls {
+        let force: Vec3 = fields
+            .iter()
+            .map(|(field_trans, field)| field_force(field, ball_trans.translation - field_trans.translation))
+            .sum();
+
+        ball_force.force += force;
+    }
+}
+
+// Shows the radius of every field, repulsing fields in red and attracting ones in blue
+pub(super) fn block_setup_debug_fields(
+    mut commands: Commands,
+    fields: Query<(Entity, &BlockField, &Transform), Without<BlockFieldDebug>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (block, field, trans) in &fields {
+        let color = if field.strength >= 0.0 {
+            Color::rgba(1.0, 0.0, 0.0, 0.15)
+        } else {
+            Color::rgba(0.0, 0.3, 1.0, 0.15)
+        };
+
+        let sphere = commands.spawn(PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::UVSphere {
+                radius: field.radius,
+                ..default()
+            })),
+            material: materials.add(StandardMaterial {
+                base_color: color,
+                alpha_mode: AlphaMode::Blend,
+                perceptual_roughness: 1.0,
+                unlit: true,
+                ..default()
+            }),
+            // Undo the block's scale so the sphere matches the radius
+            transform: Transform::from_scale(Vec3::ONE / trans.scale),
+            ..default()
+        }).id();
+
+        commands.entity(block)
+            .add_child(sphere)
+            .insert(BlockFieldDebug);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pushes_pulls_and_stacks() {
+        let repulsor = BlockField { radius: 20.0, strength: 100.0, falloff: 1.0 };
+        let attractor = BlockField { radius: 40.0, strength: -50.0, falloff: 0.0 };
+
+        // The force depends on the distance, not only on the direction
+        let near = field_force(&repulsor, Vec3::new(5.0, 0.0, 0.0));
+        let far = field_force(&repulsor, Vec3::new(15.0, 0.0, 0.0));
+        assert_eq!(near, Vec3::new(75.0, 0.0, 0.0));
+        assert_eq!(far, Vec3::new(25.0, 0.0, 0.0));
+        assert_eq!(field_force(&repulsor, Vec3::new(0.0, 0.0, 25.0)), Vec3::ZERO);
+        assert_eq!(field_force(&repulsor, Vec3::ZERO), Vec3::ZERO);
+
+        let pull = field_force(&attractor, Vec3::new(0.0, 0.0, 30.0));
+        assert_eq!(pull, Vec3::new(0.0, 0.0, -50.0));
+
+        let offset = Vec3::new(15.0, 0.0, 0.0);
+        let both = field_force(&repulsor, offset) + field_force(&attractor, offset);
+        assert_eq!(both, Vec3::new(-25.0, 0.0, 0.0));
+    }
+}
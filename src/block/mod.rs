@@ -1,6 +1,6 @@
 pub mod trigger;
+pub mod field;
 
-use std::f32::consts::TAU;
 use std::time::Duration;
 
 use bevy::app::App;
@@ -9,25 +9,29 @@ use bevy::gltf::{Gltf, GltfMesh};
 use bevy::log::info;
 use bevy::math::Vec2;
 use bevy::pbr::MaterialMeshBundle;
-use bevy::prelude::{Bundle, Color, Commands, Component, DespawnRecursiveExt, Entity, EventWriter, IntoSystemDescriptor, MaterialPlugin, Plugin, Query, Res, ResMut, SystemSet, Time, Timer, TimerMode, Transform, TransformBundle, Vec3, Visibility, With, Without};
+use bevy::prelude::{Bundle, Color, Commands, Component, DespawnRecursiveExt, Entity, EventReader, EventWriter, IntoSystemDescriptor, MaterialPlugin, Or, Plugin, Quat, Query, Res, ResMut, SystemSet, Time, Timer, TimerMode, Transform, TransformBundle, Vec3, Visibility, With, Without};
 use bevy::prelude::KeyCode::C;
-use bevy::time::FixedTimestep;
 use bevy::utils::default;
 use bevy::utils::hashbrown::HashMap;
-use bevy_rapier3d::prelude::{ActiveEvents, CoefficientCombineRule, Collider, CollisionGroups, ExternalForce, Friction, LockedAxes, Restitution, RigidBody, Sensor};
+use bevy_rapier3d::prelude::{ActiveEvents, CoefficientCombineRule, Collider, ColliderScale, CollisionGroups, Friction, LockedAxes, QueryFilter, RapierContext, Restitution, RigidBody, Sensor, Velocity};
+use serde::{Deserialize, Serialize};
 
 use crate::ball::{ActiveBall, Ball};
-use crate::block::trigger::{BlockTrigger, BlockTriggerTarget, BlockTriggerTargetInactive, TriggerGroup, TriggerState, TriggerStates, TriggerType};
-use crate::config::{BALL_RADIUS, BLOCK_DEPTH, BLOCK_HEIGHT, BLOCK_ROUNDNESS, BLOCK_WIDTH_H, COLLIDER_GROUP_ARENA, COLLIDER_GROUP_BALL, COLLIDER_GROUP_BLOCK, COLLIDER_GROUP_DEATH, MAX_RESTITUTION};
+use crate::block::field::{block_apply_fields, block_setup_debug_fields, BlockField};
+use crate::block::trigger::{auto_stop_after, BlockTrigger, BlockTriggerTarget, BlockTriggerTargetInactive, TriggerGroup, TriggerState, TriggerStates, TriggerType};
+use crate::config::{BALL_RADIUS, BLOCK_DEPTH, BLOCK_GAP, BLOCK_HEIGHT, BLOCK_ROUNDNESS, BLOCK_WIDTH, BLOCK_WIDTH_H, COLLIDER_GROUP_ARENA, COLLIDER_GROUP_BALL, COLLIDER_GROUP_BLOCK, COLLIDER_GROUP_DEATH, DEBUG_FIELDS_ENABLED, EXPLOSION_CHAIN_DELAY, EXPLOSION_DAMAGE, MAX_RESTITUTION, PORTAL_COOLDOWN, SPLITTER_CHILD_SCALE, SPLITTER_CHILD_SPEED, SPLITTER_CHILDREN};
 use crate::events::MatchEvent;
 use crate::labels::SystemLabels;
-use crate::level::RequestTag;
+use crate::level::{LevelReloaded, Levels, RequestTag};
+use crate::level::layers::layer_elevation;
 use crate::materials::block::BlockMaterial;
 use crate::MyAssetPack;
-use crate::physics::{Collidable, CollidableKind, COLLISION_EVENT_HANDLING, CollisionInfo, CollisionTag};
+use crate::r#match::state::MatchState;
+use crate::pickups::{Pickup, PickupType};
+use crate::physics::{Collidable, CollidableKind, Collision, COLLISION_EVENT_HANDLING, CollisionInfo, CollisionTag};
 use crate::state::GameState;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum BlockType {
     Simple,
     Hardling,
@@ -36,25 +40,45 @@ pub enum BlockType {
     SimpleTop,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum BlockBehaviour {
     SittingDuck,
-    Spinner,
-    Vanisher,
-    Repuslor,
+    // Degrees per second, counter clockwise seen from above
+    Spinner(f32),
+    // Seconds between appearing and vanishing, offset of the first toggle as a fraction of the period
+    Vanisher(f32, f32),
+    // Distance at which the ball is pushed away, maximum force
+    Repuslor(f32, f32),
+    // Radius, strength (negative values pull the ball in), falloff towards the radius
+    Field(f32, f32, f32),
+    // Speed
     EvaderR(f32),
     EvaderL(f32),
     EvaderU(f32),
     EvaderD(f32),
-    Portal,
+    // Degrees the ball's velocity is turned by when it comes out of this portal
+    Portal(f32),
+    // Radius in which the other blocks take a hit when this one is destroyed
+    Explosive(f32),
+    // Breaks into two smaller blocks moving apart
+    Splitter,
+    // Seconds without a hit until the block heals one hit point
+    Regenerating(f32),
 }
 
 
 #[derive(Component)]
-struct BlockSpinner;
+struct BlockSpinner {
+    // Radians per second
+    angular_speed: f32,
+}
 
 #[derive(Component)]
-struct BlockVanisher;
+struct BlockVanisher {
+    timer: Timer,
+    // Toggle is due but waits for the ball to leave the block's space
+    pending: bool,
+}
 
 #[derive(Component)]
 struct BlockEvader {
@@ -62,11 +86,32 @@ struct BlockEvader {
 }
 
 #[derive(Component)]
-struct BlockPortal;
+struct BlockPortal {
+    // Portals of the same group are a pair, a portal alone in its group is a trigger target
+    group: Option<TriggerGroup>,
+    // Radians the ball's velocity is turned by when it comes out of this portal
+    exit: f32,
+    // Seconds until the portal can be used again
+    cooldown: f32,
+    // Trigger collision the portal already teleported the ball for
+    used: Option<Collision>,
+}
+
 
+// An explosive block that went off, it damages the blocks around it once the timer finishes
 #[derive(Component)]
-struct BlockRepulsor;
+struct BlockExplosion {
+    position: Vec3,
+    radius: f32,
+    timer: Timer,
+}
 
+#[derive(Component)]
+struct BlockRegenerator {
+    delay: f32,
+    // Time of the last hit, or of the last heal
+    last_hit: Duration,
+}
 
 #[derive(Component)]
 struct Shaking {
@@ -75,15 +120,25 @@ struct Shaking {
     direction: Vec3,
 }
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone)]
 pub struct Block {
     pub position: Vec2,
+    // Radians around the y axis, counter clockwise seen from above
+    pub rotation: f32,
+    // Layer the block is stacked in, see level::layers
+    pub layer: i32,
+    // Size relative to a regular block on the x/z plane
+    pub scale: f32,
     pub asset_name: String,
     pub block_type: BlockType,
     pub behaviour: BlockBehaviour,
     pub material: Option<Handle<BlockMaterial>>,
     pub trigger_type: Option<TriggerType>,
     pub trigger_group: Option<TriggerGroup>,
+    // Name that win criteria can refer to
+    pub tag: Option<String>,
+    // Pickup that is dropped when the block is destroyed
+    pub drop: Option<PickupType>,
 }
 
 #[derive(Component, Debug)]
@@ -107,16 +162,32 @@ impl Default for Hittable {
     }
 }
 
+impl Block {
+    // Hits it takes to clear the block, including the blocks it splits into
+    pub fn block_count(&self) -> i32 {
+        match (&self.block_type, &self.behaviour) {
+            (BlockType::Obstacle, _) => 0,
+            (_, BlockBehaviour::Splitter) => 1 + SPLITTER_CHILDREN,
+            _ => 1,
+        }
+    }
+}
+
 impl Default for Block {
     fn default() -> Self {
         Block {
             position: Default::default(),
+            rotation: 0.0,
+            layer: 0,
+            scale: 1.0,
             asset_name: "003_SimpleBlock".to_string(),
             block_type: BlockType::Simple,
             behaviour: BlockBehaviour::SittingDuck,
             material: None,
             trigger_type: None,
             trigger_group: None,
+            tag: None,
+            drop: None,
         }
     }
 }
@@ -136,32 +207,38 @@ impl Plugin for BlockPlugin {
             .add_system_set(
                 SystemSet::on_update(GameState::InMatch)
                     .with_system(block_spawn.label(SystemLabels::UpdateWorld))
-                    .with_system(block_repluse.label(SystemLabels::UpdateWorld))
+                    .with_system(block_apply_fields.label(SystemLabels::UpdateWorld))
+                    .with_system(block_spin.label(SystemLabels::UpdateWorld))
                     .with_system(block_update_evader)
+                    .with_system(block_vanish.label(SystemLabels::UpdateWorld))
                     .with_system(block_shake.after(SystemLabels::UpdateWorld))
                     .with_system(block_update_custom_material)
+                    .with_system(block_update_trigger_logic)
                     .with_system(block_update_trigger_targets)
                     .with_system(block_update_portals)
+                    .with_system(block_explode.label(SystemLabels::UpdateWorld).label(SystemLabels::BlockExplosions))
+                    .with_system(block_regenerate.label(SystemLabels::UpdateWorld))
+                    .with_system(block_despawn_on_reload.after(SystemLabels::LevelReload))
 
             )
 
             .add_system_to_stage(COLLISION_EVENT_HANDLING, block_handle_collisions)
             .add_system_to_stage(COLLISION_EVENT_HANDLING, block_handle_evader_collisions)
             .add_system_to_stage(COLLISION_EVENT_HANDLING, block_handle_obstacle_trigger_collisions)
-
-            .add_system_set(
-                SystemSet::on_update(GameState::InMatch)
-                    .with_system(block_vanish)
-                    .with_system(block_spin)
-                    .with_run_criteria(FixedTimestep::step(1.0))
-                    .label(SystemLabels::UpdateWorld)
-            )
+            .add_system_to_stage(COLLISION_EVENT_HANDLING, block_handle_portal_collisions)
 
             .add_system_set(
                 SystemSet::on_exit(GameState::PostMatch)
                     .with_system(block_despawn)
             )
         ;
+
+        if DEBUG_FIELDS_ENABLED {
+            app.add_system_set(
+                SystemSet::on_update(GameState::InMatch)
+                    .with_system(block_setup_debug_fields.after(SystemLabels::UpdateWorld))
+            );
+        }
     }
 }
 
@@ -174,7 +251,11 @@ fn block_spawn(
     assets_gltf: Res<Assets<Gltf>>,
     assets_gltf_meshes: Res<Assets<GltfMesh>>,
     mut custom_materials: ResMut<Assets<BlockMaterial>>,
+    mut match_state: ResMut<MatchState>,
+    levels: Res<Levels>,
 ) {
+    let logic = levels.get_current_level().map_or(&[][..], |level| &level.triggers[..]);
+
     if let Some(gltf) = assets_gltf.get(&my.0) {
         let mesh =
             &assets_gltf_meshes.get(&gltf.named_meshes["SimpleBlock.001"]).unwrap()
@@ -184,14 +265,22 @@ fn block_spawn(
         for (entity, block) in &empties {
             let mut block_commands = commands.entity(entity);
 
+            if let Some(tag) = &block.tag {
+                if block.block_type != BlockType::Obstacle {
+                    match_state.add_tagged_block(tag);
+                }
+            }
+
 
             // .with_scale(Vec3::new(BLOCK_WIDTH_H, BLOCK_HEIGHT / 4.0, BLOCK_DEPTH / 2.0))))
             block_commands
                 .remove::<RequestTag>()
                 .insert(Collider::round_cuboid(
-                    BLOCK_WIDTH_H, BLOCK_HEIGHT / 4.0, BLOCK_DEPTH / 2.0,
+                    BLOCK_WIDTH_H * block.scale, BLOCK_HEIGHT / 4.0, BLOCK_DEPTH / 2.0 * block.scale,
                     BLOCK_ROUNDNESS,
                 ))
+                // The collider is sized above, the scale of the transform only applies to the mesh
+                .insert(ColliderScale::Absolute(Vec3::ONE))
 
                 .insert(Restitution {
                     coefficient: MAX_RESTITUTION,
@@ -215,18 +304,40 @@ fn block_spawn(
             };
 
             match block.behaviour {
-                BlockBehaviour::Spinner => {
-                    block_commands.insert(BlockSpinner);
+                BlockBehaviour::Spinner(speed) => {
+                    block_commands.insert(BlockSpinner {
+                        angular_speed: speed.to_radians(),
+                    });
+                    block_commands.insert(RigidBody::KinematicPositionBased);
+                }
+
+                BlockBehaviour::Vanisher(period, phase) => {
+                    let mut timer = Timer::from_seconds(period, TimerMode::Repeating);
+                    timer.set_elapsed(Duration::from_secs_f32(period * phase));
+
+                    block_commands.insert(BlockVanisher {
+                        timer,
+                        pending: false,
+                    });
                     block_commands.insert(RigidBody::Fixed);
                 }
 
-                BlockBehaviour::Vanisher => {
-                    block_commands.insert(BlockVanisher);
+                // A repulsor is a field that fades linearly
+                BlockBehaviour::Repuslor(threshold, max_force) => {
+                    block_commands.insert(BlockField {
+                        radius: threshold,
+                        strength: max_force,
+                        falloff: 1.0,
+                    });
                     block_commands.insert(RigidBody::Fixed);
                 }
 
-                BlockBehaviour::Repuslor => {
-                    block_commands.insert(BlockRepulsor);
+                BlockBehaviour::Field(radius, strength, falloff) => {
+                    block_commands.insert(BlockField {
+                        radius,
+                        strength,
+                        falloff,
+                    });
                     block_commands.insert(RigidBody::Fixed);
                 }
 
@@ -263,8 +374,23 @@ fn block_spawn(
                     block_commands.insert(LockedAxes::from(LockedAxes::TRANSLATION_LOCKED_Y | LockedAxes::ROTATION_LOCKED | LockedAxes::TRANSLATION_LOCKED_X));
                 }
 
-                BlockBehaviour::Portal => {
-                    block_commands.insert(BlockPortal);
+                BlockBehaviour::Portal(exit) => {
+                    block_commands.insert(BlockPortal {
+                        group: block.trigger_group,
+                        exit: exit.to_radians(),
+                        cooldown: 0.0,
+                        used: None,
+                    });
+                    block_commands.insert(RigidBody::Fixed);
+                    // The ball passes through portals
+                    block_commands.insert(Sensor);
+                }
+
+                BlockBehaviour::Regenerating(delay) => {
+                    block_commands.insert(BlockRegenerator {
+                        delay,
+                        last_hit: Duration::ZERO,
+                    });
                     block_commands.insert(RigidBody::Fixed);
                 }
 
@@ -276,12 +402,12 @@ fn block_spawn(
                 None => {}
                 Some(t) => match t {
                     TriggerType::ReceiverStartingInactive => {
-                        block_commands.insert(BlockTriggerTarget { group, auto_stop: false });
+                        block_commands.insert(BlockTriggerTarget { group, auto_stop: auto_stop_after(logic, group) });
                         block_commands.insert(BlockTriggerTargetInactive);
                     }
 
                     TriggerType::ReceiverStartingActive => {
-                        block_commands.insert(BlockTriggerTarget { group , auto_stop: false });
+                        block_commands.insert(BlockTriggerTarget { group, auto_stop: auto_stop_after(logic, group) });
                     }
 
                     _ => {
@@ -359,7 +485,11 @@ fn block_spawn(
                     material: custom_material,
                     ..default()
                 })
-                .insert(TransformBundle::from(Transform::from_xyz(block.position.x, 0.0, block.position.y)))
+                .insert(TransformBundle::from(
+                    Transform::from_xyz(block.position.x, layer_elevation(block.layer), block.position.y)
+                        .with_rotation(Quat::from_rotation_y(block.rotation))
+                        .with_scale(Vec3::new(block.scale, 1.0, block.scale))
+                ))
             ;
         }
     }
@@ -380,13 +510,36 @@ fn block_update_custom_material(
 
 fn block_despawn(
     mut commands: Commands,
-    blocks: Query<Entity, With<Block>>,
+    blocks: Query<Entity, Or<(With<Block>, With<BlockExplosion>)>>,
+    mut trigger_states: ResMut<TriggerStates>,
 ) {
     for block in &blocks {
         //info!("Despawn block {:?}", block);
         commands.entity(block)
             .despawn_recursive();
     }
+
+    // Counters and timers do not carry over to the next level
+    *trigger_states = TriggerStates::new();
+}
+
+fn block_despawn_on_reload(
+    mut commands: Commands,
+    mut events: EventReader<LevelReloaded>,
+    blocks: Query<Entity, Or<(With<Block>, With<BlockExplosion>)>>,
+    mut trigger_states: ResMut<TriggerStates>,
+) {
+    if events.iter().count() == 0 {
+        return;
+    }
+
+    for block in &blocks {
+        commands.entity(block)
+            .despawn_recursive();
+    }
+
+    // The new blocks start with fresh trigger circuits
+    *trigger_states = TriggerStates::new();
 }
 
 fn block_handle_obstacle_trigger_collisions(
@@ -400,6 +553,8 @@ fn block_handle_obstacle_trigger_collisions(
         for collision in collision {
             match collision.other {
                 CollidableKind::Ball => {
+                    triggerStates.hit(trigger.group, collision.clone());
+
                     match trigger.trigger_type {
                         TriggerType::Start => triggerStates.start(trigger.group, collision.clone()),
                         TriggerType::Stop => triggerStates.stop(trigger.group),
@@ -420,11 +575,12 @@ fn block_handle_obstacle_trigger_collisions(
 
 fn block_handle_collisions(
     mut commands: Commands,
-    mut blocks: Query<(Entity, &mut Hittable, &Block, &Transform), With<CollisionTag>>,
+    mut blocks: Query<(Entity, &mut Hittable, &Block, &Transform, Option<&mut BlockRegenerator>), With<CollisionTag>>,
     mut events: EventWriter<MatchEvent>,
     collisions: Res<CollisionInfo>,
+    time: Res<Time>,
 ) {
-    for (entity, mut hittable, block, trans) in &mut blocks {
+    for (entity, mut hittable, block, trans, mut regenerator) in &mut blocks {
         let Some(collision) = collisions.collisions.get(&entity) else { continue; };
 
         for collision in collision {
@@ -432,16 +588,26 @@ fn block_handle_collisions(
                 CollidableKind::Ball => {
                     //info!("{} {}", collision.other_pos.z, trans.translation.z);
 
-                    if hittable.only_top == true && collision.other_pos.z < trans.translation.z
+                    // Already destroyed by an explosion or another ball this frame
+                    if hittable.hit_points == 0 {
+                        continue;
+                    }
+
+                    // The top is the side facing away from the player, turned with the block
+                    let top = trans.rotation * Vec3::NEG_Z;
+
+                    if hittable.only_top == true && (collision.other_pos - trans.translation).dot(top) > 0.0
                         || hittable.only_top == false {
                         hittable.hit_points -= 1;
+
+                        if let Some(regenerator) = regenerator.as_mut() {
+                            regenerator.last_hit = time.elapsed();
+                        }
                     }
 
 
                     if hittable.hit_points == 0 {
-                        commands.entity(entity)
-                            .despawn_recursive();
-                        events.send(MatchEvent::BlockHit(collision.pos.clone(), block.block_type.clone(), block.behaviour.clone()));
+                        block_destroy(&mut commands, &mut events, entity, block, trans, 0.0);
                     } else {
                         commands.entity(entity)
                             .insert(Shaking {
@@ -457,7 +623,12 @@ fn block_handle_collisions(
                     if dir > 0.0 {
                         commands.entity(entity)
                             .despawn_recursive();
-                        events.send(MatchEvent::BlockLost);
+                        events.send(MatchEvent::BlockLost(block.tag.clone()));
+
+                        // The halves a splitter would have broken into are lost with it
+                        for _ in 1..block.block_count() {
+                            events.send(MatchEvent::BlockLost(None));
+                        }
                     }
                 }
 
@@ -468,6 +639,115 @@ fn block_handle_collisions(
 }
 
 
+// Removes a block that was hit for the last time, drops its pickup and sets it off if it is
+// explosive. Explosions go off after the delay.
+fn block_destroy(
+    commands: &mut Commands,
+    events: &mut EventWriter<MatchEvent>,
+    entity: Entity,
+    block: &Block,
+    trans: &Transform,
+    delay: f32,
+) {
+    commands.entity(entity)
+        .despawn_recursive();
+
+    if let Some(pickup_type) = block.drop {
+        commands.spawn(Pickup {
+            spawn_position: trans.translation,
+            pickup_type,
+        })
+            .insert(RequestTag);
+    }
+
+    if block.behaviour == BlockBehaviour::Splitter {
+        // The halves move apart, the parent's slot in the block count was reserved for them
+        let offset = BLOCK_WIDTH * SPLITTER_CHILD_SCALE / 2.0 + BLOCK_GAP / 2.0;
+        let children = [
+            (-offset, BlockBehaviour::EvaderL(SPLITTER_CHILD_SPEED)),
+            (offset, BlockBehaviour::EvaderR(SPLITTER_CHILD_SPEED)),
+        ];
+
+        for (offset, behaviour) in children {
+            commands
+                .spawn(Block {
+                    position: Vec2::new(trans.translation.x + offset, trans.translation.z),
+                    layer: block.layer,
+                    scale: SPLITTER_CHILD_SCALE,
+                    behaviour,
+                    ..default()
+                })
+                .insert(RequestTag);
+        }
+    }
+
+    if let BlockBehaviour::Explosive(radius) = block.behaviour {
+        commands.spawn(BlockExplosion {
+            position: trans.translation,
+            radius,
+            timer: Timer::from_seconds(delay, TimerMode::Once),
+        });
+    }
+
+    // Counted like a ball hit, so points and global pickups do not depend on what destroyed it
+    events.send(MatchEvent::BlockHit(trans.translation, block.block_type.clone(), block.behaviour.clone(), block.tag.clone()));
+}
+
+fn block_explode(
+    mut commands: Commands,
+    mut explosions: Query<(Entity, &mut BlockExplosion)>,
+    mut blocks: Query<(Entity, &mut Hittable, &Block, &Transform, Option<&mut BlockRegenerator>)>,
+    mut events: EventWriter<MatchEvent>,
+    time: Res<Time>,
+) {
+    for (entity, mut explosion) in &mut explosions {
+        if !explosion.timer.tick(time.delta()).finished() {
+            continue;
+        }
+
+        commands.entity(entity)
+            .despawn();
+
+        events.send(MatchEvent::BlockExploded(explosion.position, explosion.radius));
+
+        for (block_entity, mut hittable, block, trans, regenerator) in &mut blocks {
+            if hittable.hit_points == 0 || trans.translation.distance(explosion.position) > explosion.radius {
+                continue;
+            }
+
+            hittable.hit_points = hittable.hit_points.saturating_sub(EXPLOSION_DAMAGE);
+
+            if let Some(mut regenerator) = regenerator {
+                regenerator.last_hit = time.elapsed();
+            }
+
+            if hittable.hit_points == 0 {
+                // Explosives set off by an explosion chain with a delay
+                block_destroy(&mut commands, &mut events, block_entity, block, trans, EXPLOSION_CHAIN_DELAY);
+            }
+        }
+    }
+}
+
+
+// Heals one hit point per delay without a hit, the material shows the damage going down
+fn block_regenerate(
+    mut blocks: Query<(&mut Hittable, &mut BlockRegenerator)>,
+    time: Res<Time>,
+) {
+    for (mut hittable, mut regenerator) in &mut blocks {
+        if hittable.hit_points == 0 || hittable.hit_points >= hittable.original_hit_points {
+            continue;
+        }
+
+        if (time.elapsed() - regenerator.last_hit).as_secs_f32() >= regenerator.delay {
+            hittable.hit_points += 1;
+            regenerator.last_hit = time.elapsed();
+        }
+    }
+}
+
+
 fn block_handle_evader_collisions(
     mut commands: Commands,
     mut blocks: Query<(Entity, &mut BlockEvader), (With<Block>, With<CollisionTag>)>,
@@ -520,17 +800,21 @@ fn block_shake(
 }
 
 
+// Spinners are kinematic, rapier derives the velocity of their faces from the turning transform
 fn block_spin(
-    mut ball: Query<&mut Transform, With<BlockSpinner>>) {
-    for mut trans in &mut ball {
-        trans.rotate_y(0.25 * TAU);
+    mut spinners: Query<(&mut Transform, &BlockSpinner)>,
+    time: Res<Time>,
+) {
+    for (mut trans, spinner) in &mut spinners {
+        trans.rotate_y(spinner.angular_speed * time.delta_seconds());
     }
 }
 
 fn block_vanish(
     mut commands: Commands,
-    mut blocks: Query<(Entity, &mut Visibility, &Transform), With<BlockVanisher>>,
+    mut blocks: Query<(Entity, &mut BlockVanisher, &mut Visibility, &Transform)>,
     mut balls: Query<&Transform, With<Ball>>,
+    time: Res<Time>,
 ) {
     let mut positions: Vec<Vec3> = vec![];
 
@@ -544,7 +828,19 @@ fn block_vanish(
     let zd = BLOCK_WIDTH_H / 2.0 + BALL_RADIUS;
 
 
-    for (block, mut vis, trans) in &mut blocks {
+    for (block, mut vanisher, mut vis, trans) in &mut blocks {
+        vanisher.timer.tick(time.delta());
+
+        // An even number of toggles within one frame leaves the block as it is. A block already
+        // waiting to reappear keeps waiting, the next period does not cancel it.
+        if !vanisher.pending && vanisher.timer.times_finished_this_tick() % 2 == 1 {
+            vanisher.pending = true;
+        }
+
+        if !vanisher.pending {
+            continue;
+        }
+
         let mut can_appear = true;
 
 
@@ -561,70 +857,149 @@ fn block_vanish(
         if !vis.is_visible {
             if can_appear {
                 vis.is_visible = !vis.is_visible;
+                vanisher.pending = false;
                 commands.entity(block)
                     .remove::<Sensor>();
             }
         } else {
             vis.is_visible = !vis.is_visible;
+            vanisher.pending = false;
             commands.entity(block)
                 .insert(Sensor);
         }
     }
 }
 
-fn block_repluse(
-    blocks: Query<(&Transform), With<BlockRepulsor>>,
-    mut balls: Query<(&Transform, &mut ExternalForce), With<Ball>>,
+fn block_update_evader(
+    time: Res<Time>,
+    mut ball: Query<(&mut Transform, &mut BlockEvader), Without<BlockTriggerTargetInactive>>,
 ) {
-    let threshold = 20.0;
-    let max_repulsion = 850.0;
+    for (mut trans, mut evader) in &mut ball {
+        trans.translation += evader.velocity * time.delta().as_secs_f32();
+    }
+}
 
-    for (ball_pos, mut ball_force) in &mut balls {
-        let mut bf = Vec3::ZERO;
 
-        for block_trans in &blocks {
-            let mut direction: Vec3 = block_trans.translation - ball_pos.translation;
-            direction = direction.normalize();
-            let dist = direction.length();
+// Moves the ball to the position and turns its velocity by the exit angle. Nothing happens if
+// the ball would land inside a solid collider.
+fn portal_teleport(
+    context: &RapierContext,
+    ball: Entity,
+    ball_trans: &mut Transform,
+    velocity: &mut Velocity,
+    position: Vec3,
+    exit: f32,
+) -> bool {
+    let filter = QueryFilter::new()
+        .exclude_sensors()
+        .exclude_collider(ball);
+
+    if context.intersection_with_shape(position, Quat::IDENTITY, &Collider::ball(BALL_RADIUS), filter).is_some() {
+        return false;
+    }
 
-            if dist < threshold {
-                let f = max_repulsion / threshold * dist;
+    ball_trans.translation = position;
+    velocity.linvel = Quat::from_rotation_y(exit) * velocity.linvel;
+    true
+}
 
-                bf += direction * (f * -1.0)
-            }
+fn block_update_portals(
+    mut portals: Query<(&mut BlockPortal, &Transform, Option<&BlockTriggerTargetInactive>), Without<Ball>>,
+    mut balls: Query<(&mut Transform, &mut Velocity), (With<Ball>, With<ActiveBall>)>,
+    triggerStates: Res<TriggerStates>,
+    context: Res<RapierContext>,
+    time: Res<Time>,
+) {
+    let mut portal_counts: HashMap<TriggerGroup, usize> = HashMap::new();
+    for (portal, _, _) in &portals {
+        if let Some(group) = portal.group {
+            *portal_counts.entry(group).or_insert(0) += 1;
         }
-        ball_force.force += bf;
     }
-}
 
+    for (mut portal, block_trans, inactive) in &mut portals {
+        portal.cooldown = (portal.cooldown - time.delta_seconds()).max(0.0);
 
-fn block_update_evader(
-    time: Res<Time>,
-    mut ball: Query<(&mut Transform, &mut BlockEvader), Without<BlockTriggerTargetInactive>>,
-) {
-    for (mut trans, mut evader) in &mut ball {
-        trans.translation += evader.velocity * time.delta().as_secs_f32();
+        // Pairs are entered through the portals themselves
+        let Some(group) = portal.group else { continue; };
+        if inactive.is_some() || portal.cooldown > 0.0 || portal_counts[&group] > 1 {
+            continue;
+        }
+
+        let Some(TriggerState::Started(col)) = triggerStates.get_state(group) else { continue; };
+        if portal.used.as_ref() == Some(col) {
+            continue;
+        }
+
+        // A trigger hit teleports the ball once, whether it fits through or not
+        portal.used = Some(col.clone());
+
+        if let Ok((mut ball_trans, mut velocity)) = balls.get_mut(col.other_entity) {
+            //info!("Portaling");
+            let position = ball_trans.translation + block_trans.translation - col.pos;
+
+            if portal_teleport(&context, col.other_entity, &mut ball_trans, &mut velocity, position, portal.exit) {
+                portal.cooldown = PORTAL_COOLDOWN;
+            }
+        }
     }
 }
 
-
-fn block_update_portals(
-    mut commands: Commands,
-    portals: Query<(Entity, &BlockTriggerTarget, &Transform), (With<BlockPortal>, Without<Ball>, Without<BlockTriggerTargetInactive>)>,
-    mut balls: Query<&mut Transform, (With<Ball>, With<ActiveBall>)>,
-    mut triggerStates: ResMut<TriggerStates>,
+fn block_handle_portal_collisions(
+    mut portals: Query<(Entity, &mut BlockPortal, &Transform, Option<&BlockTriggerTargetInactive>), Without<Ball>>,
+    mut balls: Query<(&mut Transform, &mut Velocity), (With<Ball>, With<ActiveBall>)>,
+    collisions: Res<CollisionInfo>,
+    context: Res<RapierContext>,
 ) {
-    for (portal, target, block_trans) in &portals {
-        if let Some(TriggerState::Started(col)) = triggerStates.get_state(target.group) {
-            if let Ok(mut ball_trans) = balls.get_mut(col.other_entity) {
-                //info!("Portaling");
-                let d = block_trans.translation - col.pos;
-                ball_trans.translation += d;
-
-                commands.entity(portal).insert(BlockTriggerTargetInactive);
+    // Ball, entry and exit portal
+    let mut jumps = vec![];
+
+    for (entity, portal, _, inactive) in &portals {
+        let Some(collision) = collisions.collisions.get(&entity) else { continue; };
+        if portal.group.is_none() || inactive.is_some() || portal.cooldown > 0.0 {
+            continue;
+        }
+
+        let partner = portals
+            .iter()
+            .find(|(other, p, _, other_inactive)| *other != entity && p.group == portal.group && other_inactive.is_none())
+            .map(|(other, ..)| other);
+
+        let Some(partner) = partner else { continue; };
+
+        for collision in collision {
+            if collision.other == CollidableKind::Ball {
+                jumps.push((collision.other_entity, entity, partner));
+                break;
             }
         }
     }
+
+    for (ball, entry, exit) in jumps {
+        let Ok([(_, mut entry_portal, _, _), (_, mut exit_portal, exit_trans, _)]) = portals.get_many_mut([entry, exit]) else { continue; };
+
+        // Another ball went through this frame
+        if entry_portal.cooldown > 0.0 {
+            continue;
+        }
+
+        let Ok((mut ball_trans, mut velocity)) = balls.get_mut(ball) else { continue; };
+
+        if portal_teleport(&context, ball, &mut ball_trans, &mut velocity, exit_trans.translation, exit_portal.exit) {
+            entry_portal.cooldown = PORTAL_COOLDOWN;
+            exit_portal.cooldown = PORTAL_COOLDOWN;
+        }
+    }
+}
+
+fn block_update_trigger_logic(
+    levels: Res<Levels>,
+    mut trigger_states: ResMut<TriggerStates>,
+    time: Res<Time>,
+) {
+    let Some(level) = levels.get_current_level() else { return; };
+
+    trigger_states.update_logic(&level.triggers, time.delta_seconds());
 }
 
 fn block_update_trigger_targets(
@@ -640,11 +1015,11 @@ fn block_update_trigger_targets(
                 .remove::<BlockTriggerTargetInactive>();
             triggerStates.consume(target.group);
 
-            if target.auto_stop {
-                triggerStates.stop(target.group);
+            if let Some(secs) = target.auto_stop {
+                triggerStates.stop_after(target.group, secs);
             }
 
-        } else if triggerStates.is_stopped(target.group) &&
+        } else if triggerStates.get_state(target.group) == Some(&TriggerState::Stopped) &&
             !triggerStates.is_consumed(target.group) {
             //info!("Stopping receiver {:?}", target);
             commands.entity(entity)
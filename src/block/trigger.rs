@@ -1,5 +1,6 @@
 use bevy::prelude::{Bundle, Component, Resource};
 use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
 use crate::block::trigger::TriggerState::{Started, Stopped};
 use crate::physics::Collision;
 
@@ -27,9 +28,78 @@ pub struct BlockTriggerTargetInactive;
 #[derive(Component, Debug)]
 pub struct BlockTriggerTarget {
     pub group: TriggerGroup,
-    pub auto_stop: bool,
+    // Seconds after which the receiver stops its group again, set by TriggerLogic::Timed
+    pub auto_stop: Option<f32>,
 }
 
+/*
+    Logic between trigger groups, listed in the level file. Groups are given by number, named
+    groups of a layout can not be referred to.
+
+        triggers: [
+            All([1, 2], 3),       // group 3 is started while groups 1 and 2 are
+            Any([4, 5], 6),       // group 6 is started while group 4 or 5 is
+            Counter(7, 3, 8),     // group 8 starts once triggers of group 7 were hit 3 times
+            Timed(9, 5.0),        // receivers of group 9 stop it again 5 seconds after starting
+        ],
+
+    All and Any only act when their result changes, so triggers of the output group can still
+    start or stop it in between.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TriggerLogic {
+    // Input groups, output group
+    All(Vec<TriggerGroup>, TriggerGroup),
+    Any(Vec<TriggerGroup>, TriggerGroup),
+    // Input group, hits, output group
+    Counter(TriggerGroup, u32, TriggerGroup),
+    // Group, seconds
+    Timed(TriggerGroup, f32),
+}
+
+impl TriggerLogic {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            TriggerLogic::All(inputs, output) | TriggerLogic::Any(inputs, output) => {
+                if inputs.is_empty() {
+                    return Err("All and Any need at least one input group".to_string());
+                }
+
+                if inputs.contains(output) {
+                    return Err(format!("group {} can not be an input of itself", output));
+                }
+            }
+
+            TriggerLogic::Counter(input, hits, output) => {
+                if *hits == 0 {
+                    return Err("Counter needs at least one hit".to_string());
+                }
+
+                if input == output {
+                    return Err(format!("group {} can not count its own hits", output));
+                }
+            }
+
+            TriggerLogic::Timed(_, secs) => {
+                if !secs.is_finite() || *secs <= 0.0 {
+                    return Err(format!("Timed needs a positive number of seconds, got {}", secs));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Seconds after which receivers of the group stop it again
+pub fn auto_stop_after(logic: &[TriggerLogic], group: TriggerGroup) -> Option<f32> {
+    logic.iter().find_map(|l| match l {
+        TriggerLogic::Timed(g, secs) if *g == group => Some(*secs),
+        _ => None,
+    })
+}
+
+
 #[derive(PartialEq)]
 pub enum TriggerState {
     Started(Collision),
@@ -40,6 +110,12 @@ pub enum TriggerState {
 pub struct TriggerStates {
     states: HashMap<TriggerGroup, TriggerState>,
     consumed: HashMap<TriggerGroup, bool>,
+    // Ball hits on the triggers of a group and the last of them
+    hits: HashMap<TriggerGroup, (u32, Collision)>,
+    // Last result of each TriggerLogic, by its index
+    gates: HashMap<usize, bool>,
+    // Seconds until a group is stopped again
+    deadlines: HashMap<TriggerGroup, f32>,
 }
 
 impl TriggerStates {
@@ -47,6 +123,9 @@ impl TriggerStates {
         return TriggerStates {
             states: HashMap::new(),
             consumed: HashMap::new(),
+            hits: HashMap::new(),
+            gates: HashMap::new(),
+            deadlines: HashMap::new(),
         };
     }
 
@@ -114,6 +193,159 @@ impl TriggerStates {
             Some(s) => *s == Stopped
         }
     }
+
+    pub fn hit(&mut self, group: TriggerGroup, collision: Collision) {
+        let count = self.hits.get(&group).map_or(0, |(count, _)| *count);
+        self.hits.insert(group, (count + 1, collision));
+    }
+
+    pub fn stop_after(&mut self, group: TriggerGroup, secs: f32) {
+        self.deadlines.insert(group, secs);
+    }
+
+    pub fn update_logic(&mut self, logic: &[TriggerLogic], dt: f32) {
+        let mut due = vec![];
+        for (group, left) in self.deadlines.iter_mut() {
+            *left -= dt;
+            if *left <= 0.0 {
+                due.push(*group);
+            }
+        }
+
+        for group in due {
+            self.deadlines.remove(&group);
+            if self.is_started(group) {
+                self.stop(group);
+            }
+        }
+
+        for (i, gate) in logic.iter().enumerate() {
+            let last = self.gates.get(&i).copied().unwrap_or(false);
+
+            match gate {
+                TriggerLogic::All(inputs, output) | TriggerLogic::Any(inputs, output) => {
+                    let result = if let TriggerLogic::All(..) = gate {
+                        inputs.iter().all(|g| self.is_started(*g))
+                    } else {
+                        inputs.iter().any(|g| self.is_started(*g))
+                    };
+
+                    if result == last {
+                        continue;
+                    }
+
+                    self.gates.insert(i, result);
+
+                    // The output carries on with the collision that started one of the inputs
+                    let collision = inputs.iter().rev().find_map(|g| match self.states.get(g) {
+                        Some(Started(c)) => Some(c.clone()),
+                        _ => None,
+                    });
+
+                    match collision {
+                        Some(c) if result => self.start(*output, c),
+                        _ => self.stop(*output),
+                    }
+                }
+
+                TriggerLogic::Counter(input, hits, output) => {
+                    if last {
+                        continue;
+                    }
+
+                    if let Some((count, collision)) = self.hits.get(input) {
+                        if *count >= *hits {
+                            let collision = collision.clone();
+                            self.gates.insert(i, true);
+                            self.start(*output, collision);
+                        }
+                    }
+                }
+
+                // Applied by the receivers, see BlockTriggerTarget::auto_stop
+                TriggerLogic::Timed(..) => {}
+            }
+        }
+    }
 }
 
 
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{Entity, Vec3};
+
+    use crate::physics::CollidableKind;
+
+    use super::*;
+
+    fn ball_hit() -> Collision {
+        Collision {
+            other_entity: Entity::from_raw(1),
+            other: CollidableKind::Ball,
+            pos: Vec3::ZERO,
+            other_velocity: None,
+            other_pos: Vec3::ZERO,
+        }
+    }
+
+    #[test]
+    fn combines_groups() {
+        let logic = vec![
+            TriggerLogic::All(vec![1, 2], 3),
+            TriggerLogic::Any(vec![1, 2], 4),
+            TriggerLogic::Counter(5, 3, 6),
+        ];
+        let mut states = TriggerStates::new();
+
+        states.start(1, ball_hit());
+        states.update_logic(&logic, 0.1);
+        assert!(states.is_stopped(3));
+        assert!(states.is_started(4));
+
+        states.start(2, ball_hit());
+        states.update_logic(&logic, 0.1);
+        assert!(states.is_started(3));
+
+        states.stop(1);
+        states.stop(2);
+        states.update_logic(&logic, 0.1);
+        assert!(states.is_stopped(3));
+        assert!(states.is_stopped(4));
+
+        states.hit(5, ball_hit());
+        states.hit(5, ball_hit());
+        states.update_logic(&logic, 0.1);
+        assert!(states.is_stopped(6));
+
+        states.hit(5, ball_hit());
+        states.update_logic(&logic, 0.1);
+        assert!(states.is_started(6));
+
+        // A counter fires once
+        states.stop(6);
+        states.hit(5, ball_hit());
+        states.update_logic(&logic, 0.1);
+        assert!(states.is_stopped(6));
+    }
+
+    #[test]
+    fn stops_timed_groups() {
+        let logic = vec![TriggerLogic::Timed(9, 1.0)];
+        assert_eq!(auto_stop_after(&logic, 9), Some(1.0));
+        assert_eq!(auto_stop_after(&logic, 8), None);
+
+        let mut states = TriggerStates::new();
+        states.start(9, ball_hit());
+        states.stop_after(9, 1.0);
+
+        states.update_logic(&logic, 0.6);
+        assert!(states.is_started(9));
+        states.update_logic(&logic, 0.6);
+        assert!(states.is_stopped(9));
+
+        assert!(TriggerLogic::Timed(9, 0.0).validate().is_err());
+        assert!(TriggerLogic::All(vec![1, 3], 3).validate().is_err());
+        assert!(TriggerLogic::Counter(1, 0, 2).validate().is_err());
+        assert!(TriggerLogic::Any(vec![1], 2).validate().is_ok());
+    }
+}
@@ -3,6 +3,13 @@ use bevy_rapier3d::geometry::Group;
 
 pub const DEBUG_INFO_ENABLED: bool = true;
 pub const DEBUG_PHYSICS_ENABLED: bool = false && DEBUG_INFO_ENABLED;
+// Shows the radius of field blocks
+pub const DEBUG_FIELDS_ENABLED: bool = false && DEBUG_INFO_ENABLED;
+pub const LEVEL_HOT_RELOAD_ENABLED: bool = true && DEBUG_INFO_ENABLED;
+
+// Size of the level editor grid, the column count has to be odd to keep the grid centered
+pub const EDITOR_ROWS: usize = 8;
+pub const EDITOR_COLS: usize = 11;
 
 pub const SCREEN_WIDTH: f32 = 1600.0;
 pub const SCREEN_HEIGHT: f32 = 800.0;
@@ -37,6 +44,10 @@ pub const PADDLE_RESTING_Y: f32 = 0.0;
 pub const PADDLE_RESTING_X: f32 = 0.0;
 pub const PADDLE_RESTING_ROTATION: f32 = 0.0;
 
+// Distance between block layers, the ship is articulated up and down by one layer
+pub const LAYER_HEIGHT: f32 = 30.0;
+pub const MAX_LAYER: i32 = 1;
+
 pub const ARENA_WIDTH:f32 = 200.0;
 pub const ARENA_WIDTH_H:f32 = ARENA_WIDTH / 2.0;
 pub const ARENA_HEIGHT:f32 = 140.0;
@@ -63,5 +74,35 @@ pub const BLOCK_DEPTH: f32 = 7.51;
 pub const BLOCK_GAP: f32 = 2.0;
 pub const BLOCK_ROUNDNESS: f32 = 0.2;
 
+// Behaviour parameters of blocks that do not set their own
+pub const EVADER_SPEED: f32 = 50.0;
+pub const SPINNER_SPEED: f32 = 90.0;
+pub const VANISHER_PERIOD: f32 = 1.0;
+pub const REPULSOR_THRESHOLD: f32 = 20.0;
+pub const REPULSOR_FORCE: f32 = 850.0;
+pub const FIELD_RADIUS: f32 = 30.0;
+pub const FIELD_STRENGTH: f32 = -600.0;
+pub const FIELD_FALLOFF: f32 = 1.0;
+pub const EXPLOSIVE_RADIUS: f32 = 20.0;
+pub const REGENERATION_DELAY: f32 = 3.0;
+
+// Hit points an explosion takes from every block in its radius
+pub const EXPLOSION_DAMAGE: u8 = 1;
+// Seconds before an explosive set off by another explosion goes off
+pub const EXPLOSION_CHAIN_DELAY: f32 = 0.15;
+
+// Splitters break into this many blocks of the given scale, which move apart at the speed
+pub const SPLITTER_CHILDREN: i32 = 2;
+pub const SPLITTER_CHILD_SCALE: f32 = 0.5;
+pub const SPLITTER_CHILD_SPEED: f32 = 25.0;
+
+// Seconds before a portal that sent or received a ball can be used again
+pub const PORTAL_COOLDOWN: f32 = 0.5;
+
+// Points for every second left when a level with a time limit is won
+pub const TIME_BONUS_PER_SECOND: i32 = 25;
+
 pub const PICKUP_SPEED: f32 = 20.0;
 pub const PICKUP_GENERIC_SCENE: &str = "009_PU_Generic";
+
+pub const CAMPAIGN_FILE: &str = "assets/levels/campaign.ron";
@@ -0,0 +1,924 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::app::{App, Plugin};
+use bevy::log::warn;
+use bevy::prelude::{AssetServer, BackgroundColor, BuildChildren, Camera2dBundle, Color, Commands, Component, DespawnRecursiveExt, Entity, EventWriter, FlexDirection, IntoSystemDescriptor, KeyCode, NodeBundle, Quat, Query, Res, ResMut, Resource, Size, Style, SystemSet, Text, TextBundle, TextSection, TextStyle, Val, Vec2, Vec3, With, Without};
+use bevy::ui::UiRect;
+use bevy::utils::default;
+use leafwing_input_manager::InputManagerBundle;
+use leafwing_input_manager::prelude::{ActionState, InputMap};
+
+use crate::actions::EditorActions;
+use crate::block::{Block, BlockBehaviour, BlockType};
+use crate::block::trigger::TriggerType;
+use crate::config::{BLOCK_DEPTH, BLOCK_GAP, BLOCK_WIDTH, EDITOR_COLS, EDITOR_ROWS, EVADER_SPEED, EXPLOSIVE_RADIUS, FIELD_FALLOFF, FIELD_RADIUS, FIELD_STRENGTH, REGENERATION_DELAY, REPULSOR_FORCE, REPULSOR_THRESHOLD, SPINNER_SPEED, VANISHER_PERIOD};
+use crate::events::GameFlowEvent;
+use crate::level::{LevelDefinition, LevelObstacle, Levels, TargetLayout};
+use crate::level::campaign::save_level;
+use crate::level::generator::{generate_level, GeneratorSettings};
+use crate::level::layout::{block_to_slot, generate_block_grid, interpret_grid, serialize_grid, GridWriteError};
+use crate::player::Player;
+use crate::state::GameState;
+
+
+const OBSTACLE_STEP: f32 = 5.0;
+
+#[derive(Component)]
+struct EditorInput;
+
+#[derive(Component)]
+struct UITag;
+
+#[derive(Component)]
+struct EditorCell {
+    index: usize,
+}
+
+#[derive(Component)]
+struct EditorCellText {
+    index: usize,
+}
+
+#[derive(Component)]
+struct EditorInfo;
+
+
+#[derive(Resource)]
+pub struct EditorState {
+    // Row major, EDITOR_ROWS x EDITOR_COLS
+    cells: Vec<Option<Block>>,
+    positions: Vec<Vec2>,
+    // Layouts with an even number of columns sit half a step off the editor grid, which is then
+    // moved left by half a step. Its first column lies outside the arena and is not used.
+    half_step: bool,
+    obstacles: Vec<LevelObstacle>,
+    gap: f32,
+    cursor_row: usize,
+    cursor_col: usize,
+
+    // Level whose layout is loaded into the editor
+    loaded_level: Option<usize>,
+    // Layouts the editor can not show are not written back, nor are layouts it could not load
+    // completely
+    editable: bool,
+    dropped: usize,
+    play_testing: bool,
+    leave_play_test: bool,
+    message: String,
+
+    // Next level the generator builds
+    seed: u64,
+    difficulty: f32,
+}
+
+impl Default for EditorState {
+    fn default() -> Self {
+        EditorState {
+            cells: vec![None; EDITOR_ROWS * EDITOR_COLS],
+            positions: generate_block_grid(EDITOR_ROWS, EDITOR_COLS, BLOCK_GAP),
+            half_step: false,
+            obstacles: vec![],
+            gap: BLOCK_GAP,
+            cursor_row: 0,
+            cursor_col: EDITOR_COLS / 2,
+            loaded_level: None,
+            editable: true,
+            dropped: 0,
+            play_testing: false,
+            leave_play_test: false,
+            message: String::new(),
+            seed: 1,
+            difficulty: 0.5,
+        }
+    }
+}
+
+impl EditorState {
+    fn cursor(&self) -> usize {
+        self.cursor_row * EDITOR_COLS + self.cursor_col
+    }
+
+    fn cursor_position(&self) -> Vec2 {
+        self.positions[self.cursor()]
+    }
+
+    fn first_col(&self) -> usize {
+        if self.half_step { 1 } else { 0 }
+    }
+
+    fn x_offset(&self) -> f32 {
+        if self.half_step { -(BLOCK_WIDTH + self.gap) / 2.0 } else { 0.0 }
+    }
+
+    // The grid is centered on x = 0, or half a step left of it, and starts at the first row, so
+    // every block of a layout lands on the nearest editor cell.
+    fn cell_at(&self, position: Vec2) -> Option<usize> {
+        let x_step = BLOCK_WIDTH + self.gap;
+        let y_step = BLOCK_DEPTH + self.gap;
+        let y0 = self.positions[0].y;
+
+        let col = ((position.x - self.x_offset()) / x_step).round() as i32 + (EDITOR_COLS / 2) as i32;
+        let row = ((position.y - y0) / y_step).round() as i32;
+
+        if col < self.first_col() as i32 || row < 0 || col as usize >= EDITOR_COLS || row as usize >= EDITOR_ROWS {
+            return None;
+        }
+
+        Some(row as usize * EDITOR_COLS + col as usize)
+    }
+
+    fn load(&mut self, level: &LevelDefinition, index: usize) {
+        self.gap = match &level.targets {
+            TargetLayout::FilledGrid(_, _, _, _, gap) | TargetLayout::SparseGrid(_, gap) => *gap,
+            TargetLayout::HexGrid(_, _) | TargetLayout::Radial(_, _) |
+            TargetLayout::Freeform(_) | TargetLayout::Custom(_) => BLOCK_GAP,
+        };
+
+        self.cells = vec![None; EDITOR_ROWS * EDITOR_COLS];
+        self.obstacles = level.obstacles.clone();
+        self.loaded_level = Some(index);
+        self.message = String::new();
+        self.editable = true;
+        self.dropped = 0;
+
+        let blocks = match &level.targets {
+            TargetLayout::FilledGrid(cols, rows, block_type, behaviour, gap) => {
+                generate_block_grid(*rows, *cols, *gap)
+                    .into_iter()
+                    .map(|position| Block {
+                        position,
+                        block_type: block_type.clone(),
+                        behaviour: behaviour.clone(),
+                        ..default()
+                    })
+                    .collect()
+            }
+
+            TargetLayout::SparseGrid(layout, gap) => match interpret_grid(layout, *gap, false) {
+                Ok(blocks) => blocks,
+                Err(e) => {
+                    self.message = format!("Layout not loaded, {}", e);
+                    self.editable = false;
+                    vec![]
+                }
+            },
+
+            TargetLayout::HexGrid(_, _) | TargetLayout::Radial(_, _) | TargetLayout::Freeform(_) => {
+                self.message = "Only grid layouts can be edited".to_string();
+                self.editable = false;
+                vec![]
+            }
+
+            TargetLayout::Custom(name) => {
+                self.message = format!("Custom layout {} can not be edited", name);
+                self.editable = false;
+                vec![]
+            }
+        };
+
+        // The first block tells the column parity, twice its offset from the center in steps is
+        // odd for layouts with an even number of columns
+        let x_step = BLOCK_WIDTH + self.gap;
+        self.half_step = blocks
+            .first()
+            .map_or(false, |block: &Block| (2.0 * block.position.x / x_step).round() as i32 % 2 != 0);
+
+        let offset = self.x_offset();
+        self.positions = generate_block_grid(EDITOR_ROWS, EDITOR_COLS, self.gap)
+            .into_iter()
+            .map(|p| Vec2::new(p.x + offset, p.y))
+            .collect();
+
+        self.cursor_col = self.cursor_col.max(self.first_col());
+
+        for mut block in blocks {
+            match self.cell_at(block.position) {
+                Some(index) if self.cells[index].is_none() => {
+                    block.position = self.positions[index];
+                    self.cells[index] = Some(block);
+                }
+
+                _ => {
+                    warn!("No free editor cell for block at {:?}", block.position);
+                    self.dropped += 1;
+                }
+            }
+        }
+
+        if self.dropped > 0 {
+            self.message = format!("{} blocks do not fit the editor grid, the level is read only", self.dropped);
+        }
+    }
+
+    // Writing back a layout that was not loaded completely would lose blocks
+    fn check_editable(&self) -> Result<(), String> {
+        if !self.editable {
+            return Err("This layout can not be edited, it is not saved or play tested".to_string());
+        }
+
+        if self.dropped > 0 {
+            return Err(format!("{} blocks do not fit the editor grid, saving would lose them", self.dropped));
+        }
+
+        Ok(())
+    }
+
+    fn apply_to(&self, level: &mut LevelDefinition) -> Result<(), GridWriteError> {
+        let blocks: Vec<Block> = self.cells.iter().flatten().cloned().collect();
+
+        level.targets = TargetLayout::SparseGrid(serialize_grid(&blocks, self.gap)?, self.gap);
+        level.obstacles = self.obstacles.clone();
+
+        Ok(())
+    }
+
+    fn obstacle_at_cursor(&self) -> Option<usize> {
+        let p = self.cursor_position();
+        self.obstacles.iter().position(|o| obstacle_covers(o, p))
+    }
+}
+
+
+fn obstacle_covers(obstacle: &LevelObstacle, p: Vec2) -> bool {
+    match obstacle {
+        LevelObstacle::Box(center, w, h) |
+        LevelObstacle::Lift(center, w, h, _, _) => {
+            (p.x - center.x).abs() <= w / 2.0 && (p.y - center.z).abs() <= h / 2.0
+        }
+
+        // Moving boxes are picked at the place they start from
+        LevelObstacle::PathBox(waypoints, w, h, _, _) => match waypoints.first() {
+            Some(start) => (p.x - start.x).abs() <= w / 2.0 && (p.y - start.z).abs() <= h / 2.0,
+            None => false,
+        }
+
+        LevelObstacle::RotatingBox(pivot, offset, w, h, _, _) => {
+            let center = *pivot + *offset;
+            (p.x - center.x).abs() <= w / 2.0 && (p.y - center.z).abs() <= h / 2.0
+        }
+
+        LevelObstacle::ForceField(origin, normal, size, _) |
+        LevelObstacle::DirectionalDeathTrigger(origin, normal, size) => {
+            let d = Vec3::new(p.x, 0.0, p.y) - *origin;
+            let tangent = normal.cross(Vec3::Y).normalize_or_zero();
+
+            d.dot(tangent).abs() <= size / 2.0 && d.dot(normal.normalize_or_zero()).abs() <= BLOCK_DEPTH / 2.0
+        }
+    }
+}
+
+fn next_block_type(block_type: &BlockType) -> Option<BlockType> {
+    match block_type {
+        BlockType::Simple => Some(BlockType::Hardling),
+        BlockType::Hardling => Some(BlockType::Concrete),
+        BlockType::Concrete => Some(BlockType::SimpleTop),
+        BlockType::SimpleTop => Some(BlockType::Obstacle),
+        BlockType::Obstacle => None,
+    }
+}
+
+fn next_behaviour(behaviour: &BlockBehaviour) -> BlockBehaviour {
+    match behaviour {
+        BlockBehaviour::SittingDuck => BlockBehaviour::Spinner(SPINNER_SPEED),
+        BlockBehaviour::Spinner(_) => BlockBehaviour::Vanisher(VANISHER_PERIOD, 0.0),
+        BlockBehaviour::Vanisher(_, _) => BlockBehaviour::Repuslor(REPULSOR_THRESHOLD, REPULSOR_FORCE),
+        BlockBehaviour::Repuslor(_, _) => BlockBehaviour::Field(FIELD_RADIUS, FIELD_STRENGTH, FIELD_FALLOFF),
+        BlockBehaviour::Field(_, _, _) => BlockBehaviour::EvaderR(EVADER_SPEED),
+        BlockBehaviour::EvaderR(s) => BlockBehaviour::EvaderL(*s),
+        BlockBehaviour::EvaderL(s) => BlockBehaviour::EvaderU(*s),
+        BlockBehaviour::EvaderU(s) => BlockBehaviour::EvaderD(*s),
+        BlockBehaviour::EvaderD(_) => BlockBehaviour::Portal(0.0),
+        BlockBehaviour::Portal(_) => BlockBehaviour::Explosive(EXPLOSIVE_RADIUS),
+        BlockBehaviour::Explosive(_) => BlockBehaviour::Splitter,
+        BlockBehaviour::Splitter => BlockBehaviour::Regenerating(REGENERATION_DELAY),
+        BlockBehaviour::Regenerating(_) => BlockBehaviour::SittingDuck,
+    }
+}
+
+fn next_trigger_type(trigger_type: &Option<TriggerType>) -> Option<TriggerType> {
+    match trigger_type {
+        None => Some(TriggerType::Start),
+        Some(TriggerType::Start) => Some(TriggerType::Stop),
+        Some(TriggerType::Stop) => Some(TriggerType::StartStop),
+        Some(TriggerType::StartStop) => Some(TriggerType::ReceiverStartingInactive),
+        Some(TriggerType::ReceiverStartingInactive) => Some(TriggerType::ReceiverStartingActive),
+        Some(TriggerType::ReceiverStartingActive) => None,
+    }
+}
+
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(EditorState::default())
+
+            .add_startup_system(editor_spawn_input)
+
+            .add_system_set(
+                SystemSet::on_enter(GameState::Editor)
+                    .with_system(editor_load_level)
+                    .with_system(editor_spawn_ui)
+            )
+
+            .add_system_set(
+                SystemSet::on_update(GameState::Editor)
+                    .with_system(editor_handle_cells)
+                    .with_system(editor_handle_obstacles)
+                    .with_system(editor_handle_save.after(editor_handle_cells).after(editor_handle_obstacles))
+                    .with_system(editor_handle_play_test.after(editor_handle_cells).after(editor_handle_obstacles))
+                    .with_system(editor_handle_navigation.after(editor_handle_save))
+                    .with_system(editor_update_ui.after(editor_handle_navigation))
+            )
+
+            .add_system_set(
+                SystemSet::on_update(GameState::InMatch)
+                    .with_system(editor_end_play_test)
+            )
+
+            .add_system_set(
+                SystemSet::on_update(GameState::PostMatch)
+                    .with_system(editor_return_from_play_test)
+            )
+
+            .add_system_set(
+                SystemSet::on_exit(GameState::Editor)
+                    .with_system(editor_despawn_ui)
+            )
+        ;
+    }
+}
+
+
+fn editor_spawn_input(mut commands: Commands) {
+    commands
+        .spawn(InputManagerBundle::<EditorActions> {
+            action_state: ActionState::default(),
+            input_map: InputMap::default()
+                .insert(KeyCode::Up, EditorActions::CursorUp)
+                .insert(KeyCode::Down, EditorActions::CursorDown)
+                .insert(KeyCode::Left, EditorActions::CursorLeft)
+                .insert(KeyCode::Right, EditorActions::CursorRight)
+
+                .insert(KeyCode::T, EditorActions::CycleType)
+                .insert(KeyCode::B, EditorActions::CycleBehaviour)
+                .insert(KeyCode::R, EditorActions::CycleTrigger)
+                .insert(KeyCode::G, EditorActions::CycleTriggerGroup)
+                .insert(KeyCode::Back, EditorActions::ClearCell)
+
+                .insert(KeyCode::O, EditorActions::AddBox)
+                .insert(KeyCode::F, EditorActions::AddForceField)
+                .insert(KeyCode::Q, EditorActions::RotateObstacle)
+                .insert(KeyCode::LBracket, EditorActions::ShrinkObstacleWidth)
+                .insert(KeyCode::RBracket, EditorActions::GrowObstacleWidth)
+                .insert(KeyCode::Minus, EditorActions::ShrinkObstacleDepth)
+                .insert(KeyCode::Equals, EditorActions::GrowObstacleDepth)
+                .insert(KeyCode::Delete, EditorActions::RemoveObstacle)
+
+                .insert(KeyCode::F2, EditorActions::Save)
+                .insert(KeyCode::F5, EditorActions::PlayTest)
+                .insert(KeyCode::F6, EditorActions::BackToEditor)
+                .insert(KeyCode::N, EditorActions::Generate)
+                .insert(KeyCode::D, EditorActions::CycleDifficulty)
+                .insert(KeyCode::PageUp, EditorActions::PreviousLevel)
+                .insert(KeyCode::PageDown, EditorActions::NextLevel)
+                .insert(KeyCode::M, EditorActions::Exit)
+                .build(),
+        })
+        .insert(EditorInput);
+}
+
+
+fn editor_load_level(
+    mut state: ResMut<EditorState>,
+    levels: Res<Levels>,
+) {
+    state.play_testing = false;
+    state.leave_play_test = false;
+
+    // Coming back from a play test keeps the edits
+    if state.loaded_level == Some(levels.current_level) {
+        return;
+    }
+
+    if let Some(level) = levels.get_current_level() {
+        state.load(level, levels.current_level);
+    }
+}
+
+
+fn editor_handle_cells(
+    mut state: ResMut<EditorState>,
+    actions: Query<&ActionState<EditorActions>, With<EditorInput>>,
+) {
+    let Ok(action) = actions.get_single() else { return; };
+
+    if action.just_pressed(EditorActions::CursorUp) && state.cursor_row > 0 {
+        state.cursor_row -= 1;
+    }
+    if action.just_pressed(EditorActions::CursorDown) && state.cursor_row < EDITOR_ROWS - 1 {
+        state.cursor_row += 1;
+    }
+    if action.just_pressed(EditorActions::CursorLeft) && state.cursor_col > state.first_col() {
+        state.cursor_col -= 1;
+    }
+    if action.just_pressed(EditorActions::CursorRight) && state.cursor_col < EDITOR_COLS - 1 {
+        state.cursor_col += 1;
+    }
+
+    let index = state.cursor();
+    let position = state.cursor_position();
+    let cell = &mut state.cells[index];
+
+    if action.just_pressed(EditorActions::CycleType) {
+        *cell = match cell.take() {
+            None => Some(Block {
+                position,
+                ..default()
+            }),
+
+            // Obstacles can not be destroyed, so they do not keep a tag or drop
+            Some(block) => next_block_type(&block.block_type).map(|block_type| Block {
+                tag: block.tag.clone().filter(|_| block_type != BlockType::Obstacle),
+                drop: block.drop.filter(|_| block_type != BlockType::Obstacle),
+                block_type,
+                ..block
+            })
+        };
+    }
+
+    if action.just_pressed(EditorActions::ClearCell) {
+        *cell = None;
+    }
+
+    let Some(block) = cell else { return; };
+
+    if action.just_pressed(EditorActions::CycleBehaviour) {
+        block.behaviour = next_behaviour(&block.behaviour);
+    }
+
+    if action.just_pressed(EditorActions::CycleTrigger) {
+        block.trigger_type = next_trigger_type(&block.trigger_type);
+        block.trigger_group = block.trigger_type.as_ref().map(|_| block.trigger_group.unwrap_or(0));
+    }
+
+    if action.just_pressed(EditorActions::CycleTriggerGroup) {
+        if let Some(g) = block.trigger_group {
+            block.trigger_group = Some((g + 1) % 10);
+        }
+    }
+}
+
+
+fn editor_handle_obstacles(
+    mut state: ResMut<EditorState>,
+    actions: Query<&ActionState<EditorActions>, With<EditorInput>>,
+) {
+    let Ok(action) = actions.get_single() else { return; };
+
+    let p = state.cursor_position();
+    let origin = Vec3::new(p.x, 0.0, p.y);
+
+    if action.just_pressed(EditorActions::AddBox) {
+        state.obstacles.push(LevelObstacle::Box(origin, BLOCK_WIDTH, BLOCK_DEPTH));
+    }
+
+    if action.just_pressed(EditorActions::AddForceField) {
+        state.obstacles.push(LevelObstacle::ForceField(origin, Vec3::Z, 3.0 * BLOCK_WIDTH, false));
+    }
+
+    let Some(index) = state.obstacle_at_cursor() else { return; };
+
+    if action.just_pressed(EditorActions::RemoveObstacle) {
+        state.obstacles.remove(index);
+        return;
+    }
+
+    let width = if action.just_pressed(EditorActions::GrowObstacleWidth) {
+        OBSTACLE_STEP
+    } else if action.just_pressed(EditorActions::ShrinkObstacleWidth) {
+        -OBSTACLE_STEP
+    } else {
+        0.0
+    };
+
+    let depth = if action.just_pressed(EditorActions::GrowObstacleDepth) {
+        OBSTACLE_STEP
+    } else if action.just_pressed(EditorActions::ShrinkObstacleDepth) {
+        -OBSTACLE_STEP
+    } else {
+        0.0
+    };
+
+    let rotate = action.just_pressed(EditorActions::RotateObstacle);
+
+    match &mut state.obstacles[index] {
+        LevelObstacle::Box(_, w, h) |
+        LevelObstacle::PathBox(_, w, h, _, _) |
+        LevelObstacle::Lift(_, w, h, _, _) => {
+            *w = (*w + width).max(OBSTACLE_STEP);
+            *h = (*h + depth).max(OBSTACLE_STEP);
+
+            if rotate {
+                std::mem::swap(w, h);
+            }
+        }
+
+        LevelObstacle::RotatingBox(_, offset, w, h, _, _) => {
+            *w = (*w + width).max(OBSTACLE_STEP);
+            *h = (*h + depth).max(OBSTACLE_STEP);
+
+            if rotate {
+                *offset = Quat::from_rotation_y(FRAC_PI_2) * *offset;
+            }
+        }
+
+        LevelObstacle::ForceField(_, normal, size, _) |
+        LevelObstacle::DirectionalDeathTrigger(_, normal, size) => {
+            *size = (*size + width).max(OBSTACLE_STEP);
+
+            if rotate {
+                *normal = Quat::from_rotation_y(FRAC_PI_2) * *normal;
+            }
+        }
+    }
+}
+
+
+fn editor_handle_save(
+    mut state: ResMut<EditorState>,
+    mut levels: ResMut<Levels>,
+    actions: Query<&ActionState<EditorActions>, With<EditorInput>>,
+) {
+    let Ok(action) = actions.get_single() else { return; };
+
+    if !action.just_pressed(EditorActions::Save) {
+        return;
+    }
+
+    if let Err(e) = state.check_editable() {
+        state.message = e;
+        return;
+    }
+
+    let current = levels.current_level;
+    let Some(path) = levels.sources.get(current).cloned() else { return; };
+    let Some(level) = levels.get_current_level_mut() else { return; };
+
+    state.message = match state.apply_to(level) {
+        Ok(_) => match save_level(&path, level) {
+            Ok(_) => format!("Saved {}", path.display()),
+            Err(e) => e.to_string(),
+        },
+
+        Err(e) => e.to_string(),
+    };
+}
+
+
+fn editor_handle_play_test(
+    mut state: ResMut<EditorState>,
+    mut levels: ResMut<Levels>,
+    mut players: Query<&mut Player>,
+    actions: Query<&ActionState<EditorActions>, With<EditorInput>>,
+    mut game_flow: EventWriter<GameFlowEvent>,
+) {
+    let Ok(action) = actions.get_single() else { return; };
+
+    if !action.just_pressed(EditorActions::PlayTest) {
+        return;
+    }
+
+    if let Err(e) = state.check_editable() {
+        state.message = e;
+        return;
+    }
+
+    let Some(level) = levels.get_current_level_mut() else { return; };
+
+    if let Err(e) = state.apply_to(level) {
+        state.message = e.to_string();
+        return;
+    }
+
+    for mut player in &mut players {
+        player.set_balls(3);
+    }
+
+    state.play_testing = true;
+    game_flow.send(GameFlowEvent::StartMatch);
+}
+
+
+// Ending the match first lets the usual PostMatch clean up despawn the level
+fn editor_end_play_test(
+    mut state: ResMut<EditorState>,
+    actions: Query<&ActionState<EditorActions>, With<EditorInput>>,
+    mut game_flow: EventWriter<GameFlowEvent>,
+) {
+    let Ok(action) = actions.get_single() else { return; };
+
+    if state.play_testing && !state.leave_play_test && action.just_pressed(EditorActions::BackToEditor) {
+        state.leave_play_test = true;
+        game_flow.send(GameFlowEvent::PlayerLooses);
+    }
+}
+
+
+fn editor_return_from_play_test(
+    state: Res<EditorState>,
+    actions: Query<&ActionState<EditorActions>, With<EditorInput>>,
+    mut game_flow: EventWriter<GameFlowEvent>,
+) {
+    let Ok(action) = actions.get_single() else { return; };
+
+    if state.play_testing && (state.leave_play_test || action.just_pressed(EditorActions::BackToEditor)) {
+        game_flow.send(GameFlowEvent::OpenEditor);
+    }
+}
+
+
+fn editor_handle_navigation(
+    mut state: ResMut<EditorState>,
+    mut levels: ResMut<Levels>,
+    actions: Query<&ActionState<EditorActions>, With<EditorInput>>,
+    mut game_flow: EventWriter<GameFlowEvent>,
+) {
+    let Ok(action) = actions.get_single() else { return; };
+
+    if action.just_pressed(EditorActions::Exit) {
+        game_flow.send(GameFlowEvent::StartGame);
+        return;
+    }
+
+    let current = levels.current_level;
+
+    if action.just_pressed(EditorActions::PreviousLevel) && current > 0 {
+        levels.current_level -= 1;
+    }
+
+    if action.just_pressed(EditorActions::NextLevel) && current + 1 < levels.definitions.len() {
+        levels.current_level += 1;
+    }
+
+    // Unsaved edits of the previous level are dropped
+    if levels.current_level != current {
+        let index = levels.current_level;
+        state.load(&levels.definitions[index], index);
+    }
+
+    if action.just_pressed(EditorActions::CycleDifficulty) {
+        state.difficulty = if state.difficulty >= 1.0 { 0.0 } else { state.difficulty + 0.25 };
+        state.message = format!("Generator difficulty {}", state.difficulty);
+    }
+
+    if action.just_pressed(EditorActions::Generate) {
+        let index = levels.current_level;
+        let seed = state.seed;
+        let difficulty = state.difficulty;
+        let Some(level) = levels.get_current_level_mut() else { return; };
+
+        let mut generated = generate_level(seed, &GeneratorSettings::for_difficulty(difficulty));
+        generated.background_asset = level.background_asset.clone();
+        *level = generated;
+
+        state.load(level, index);
+        state.message = format!("Generated seed {} at difficulty {}", seed, difficulty);
+        state.seed += 1;
+    }
+}
+
+
+fn editor_spawn_ui(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let style = TextStyle {
+        font: asset_server.load("fonts/Orbitron-Regular.ttf"),
+        font_size: 20.0,
+        color: Color::ANTIQUE_WHITE,
+    };
+
+    commands
+        .spawn(Camera2dBundle::default())
+        .insert(UITag);
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(20.0)),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            for row in 0..EDITOR_ROWS {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Row,
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        for col in 0..EDITOR_COLS {
+                            let index = row * EDITOR_COLS + col;
+
+                            parent
+                                .spawn(NodeBundle {
+                                    style: Style {
+                                        size: Size::new(Val::Px(64.0), Val::Px(32.0)),
+                                        margin: UiRect::all(Val::Px(2.0)),
+                                        padding: UiRect::all(Val::Px(4.0)),
+                                        ..default()
+                                    },
+                                    ..default()
+                                })
+                                .insert(EditorCell { index })
+                                .with_children(|parent| {
+                                    parent
+                                        .spawn(TextBundle::from_section("..", style.clone()))
+                                        .insert(EditorCellText { index });
+                                });
+                        }
+                    });
+            }
+
+            parent
+                .spawn(TextBundle::from_sections([
+                    TextSection::new("", style.clone()),
+                    TextSection::new("", style.clone()),
+                    TextSection::new("", TextStyle {
+                        color: Color::GOLD,
+                        ..style.clone()
+                    }),
+                    TextSection::new(
+                        "\n\nArrows: move   T: type   B: behaviour   R: trigger   G: trigger group   Backspace: clear\n\
+                         O: box   F: force field   Q: rotate   [ ]: width   - =: depth   Del: remove obstacle\n\
+                         F2: save   F5: play test   F6: back to editor   PgUp PgDn: level   M: menu\n\
+                         N: generate level   D: generator difficulty",
+                        TextStyle {
+                            font_size: 16.0,
+                            ..style.clone()
+                        },
+                    ),
+                ]).with_style(Style {
+                    margin: UiRect::top(Val::Px(20.0)),
+                    ..default()
+                }))
+                .insert(EditorInfo);
+        })
+        .insert(UITag);
+}
+
+
+fn editor_update_ui(
+    state: Res<EditorState>,
+    mut cells: Query<(&EditorCell, &mut BackgroundColor)>,
+    mut texts: Query<(&EditorCellText, &mut Text)>,
+    mut info: Query<&mut Text, (With<EditorInfo>, Without<EditorCellText>)>,
+) {
+    let cursor = state.cursor();
+
+    for (cell, mut color) in &mut cells {
+        let covered = state.obstacles.iter().any(|o| obstacle_covers(o, state.positions[cell.index]));
+
+        *color = if cell.index == cursor {
+            Color::RED
+        } else if covered {
+            Color::rgb(0.2, 0.3, 0.6)
+        } else {
+            Color::rgba(1.0, 1.0, 1.0, 0.1)
+        }.into();
+    }
+
+    for (cell, mut text) in &mut texts {
+        text.sections[0].value = match &state.cells[cell.index] {
+            None => "..".to_string(),
+            Some(block) => block_to_slot(block).unwrap_or_else(|_| "??".to_string()),
+        };
+    }
+
+    if let Ok(mut text) = info.get_single_mut() {
+        let level = state.loaded_level.unwrap_or_default();
+
+        text.sections[0].value = match &state.cells[cursor] {
+            None => format!("Level {}, row {}, column {}: empty", level, state.cursor_row + 1, state.cursor_col + 1),
+            Some(block) => format!(
+                "Level {}, row {}, column {}: {:?}, {:?}, trigger {:?} in group {:?}",
+                level, state.cursor_row + 1, state.cursor_col + 1,
+                block.block_type, block.behaviour, block.trigger_type, block.trigger_group
+            ),
+        };
+
+        text.sections[1].value = match state.obstacle_at_cursor() {
+            None => "\nNo obstacle".to_string(),
+            Some(index) => match &state.obstacles[index] {
+                LevelObstacle::Box(_, w, h) => format!("\nBox {} x {}", w, h),
+                LevelObstacle::ForceField(_, normal, size, _) => format!("\nForce field {} facing {:?}", size, normal),
+                LevelObstacle::DirectionalDeathTrigger(_, normal, size) => format!("\nDeath trigger {} facing {:?}", size, normal),
+                LevelObstacle::PathBox(waypoints, w, h, speed, group) => format!("\nPath box {} x {} through {} waypoints at {}, group {:?}", w, h, waypoints.len(), speed, group),
+                LevelObstacle::RotatingBox(_, _, w, h, speed, group) => format!("\nRotating box {} x {} at {} rad/s, group {:?}", w, h, speed, group),
+                LevelObstacle::Lift(_, w, h, a, b) => format!("\nLift {} x {} between layers {} and {}", w, h, a, b),
+            }
+        };
+
+        text.sections[2].value = format!("\n{}", state.message);
+    }
+}
+
+
+fn editor_despawn_ui(
+    mut commands: Commands,
+    ui: Query<Entity, With<UITag>>,
+) {
+    for ui in &ui {
+        commands.entity(ui)
+            .despawn_recursive();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_survive_a_round_trip() {
+        let level = LevelDefinition {
+            targets: TargetLayout::SparseGrid("AA .. AA\n BB ZA AC".to_string(), 2.0),
+            ..default()
+        };
+
+        let mut state = EditorState::default();
+        state.load(&level, 0);
+        assert_eq!(state.cells.iter().flatten().count(), 5);
+
+        state.cursor_row = 0;
+        state.cursor_col = EDITOR_COLS / 2;
+        let index = state.cursor();
+        assert!(state.cells[index].is_none());
+        state.cells[index] = Some(Block {
+            position: state.cursor_position(),
+            trigger_type: Some(TriggerType::Start),
+            trigger_group: Some(3),
+            ..default()
+        });
+
+        let mut edited = LevelDefinition::default();
+        state.apply_to(&mut edited).unwrap();
+
+        let TargetLayout::SparseGrid(layout, gap) = &edited.targets else { panic!("not a sparse grid"); };
+        assert_eq!(interpret_grid(layout, *gap, true).unwrap().len(), 6);
+        assert!(layout.starts_with("AA AAA3 AA"), "{}", layout);
+    }
+
+    #[test]
+    fn keeps_even_layouts_in_place() {
+        for layout in ["AA AA", "AA .. AA AA\nBA .. .. BA"] {
+            let level = LevelDefinition {
+                targets: TargetLayout::SparseGrid(layout.to_string(), 2.0),
+                ..default()
+            };
+
+            let mut state = EditorState::default();
+            state.load(&level, 0);
+            assert!(state.half_step);
+
+            let mut saved = LevelDefinition::default();
+            state.apply_to(&mut saved).unwrap();
+
+            let TargetLayout::SparseGrid(saved, gap) = &saved.targets else { panic!("not a sparse grid"); };
+            let blocks = |layout: &str| interpret_grid(&layout.to_string(), *gap, true)
+                .unwrap()
+                .into_iter()
+                .map(|b| (b.position, b.block_type))
+                .collect::<Vec<_>>();
+            assert_eq!(blocks(saved), blocks(layout), "{}", saved);
+        }
+    }
+
+    #[test]
+    fn refuses_to_save_what_it_could_not_load() {
+        let mut state = EditorState::default();
+
+        state.load(&LevelDefinition { targets: TargetLayout::Custom("Conveyor".to_string()), ..default() }, 0);
+        assert!(state.check_editable().is_err());
+
+        let wide = vec!["AA"; EDITOR_COLS + 2].join(" ");
+        state.load(&LevelDefinition { targets: TargetLayout::SparseGrid(wide, 2.0), ..default() }, 0);
+        assert_eq!(state.dropped, 2);
+        assert!(state.check_editable().is_err());
+
+        let hex = LevelDefinition { targets: TargetLayout::HexGrid("AA AA\nAA".to_string(), 2.0), ..default() };
+        state.load(&hex, 0);
+        assert!(state.check_editable().is_err());
+
+        state.load(&LevelDefinition::default(), 0);
+        assert!(state.check_editable().is_ok());
+    }
+}
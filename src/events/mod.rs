@@ -1,11 +1,12 @@
 use std::os::macos::raw::stat;
 use bevy::app::{App, Plugin};
 use bevy::log::info;
-use bevy::prelude::{Commands, Entity, EventReader, EventWriter, IntoSystemDescriptor, Query, ResMut, State, SystemSet, Vec3};
+use bevy::prelude::{Commands, Entity, EventReader, EventWriter, IntoSystemDescriptor, Query, Res, ResMut, State, SystemSet, Vec3};
 use crate::ball::Ball;
 use crate::block::{BlockBehaviour, BlockType};
 use crate::labels::SystemLabels;
 use crate::level::{LevelDefinition, Levels, RequestTag, WinCriteria};
+use crate::level::criteria::CriteriaState;
 use crate::pickups::{Pickup, PickupType};
 use crate::player::{Player, PlayerState};
 use crate::points::{PointsDisplay, PointsDisplayRequest};
@@ -23,6 +24,8 @@ pub enum GameFlowEvent {
 
     NextLevel,
 
+    OpenEditor,
+
     EndGame,
 }
 
@@ -32,10 +35,14 @@ pub enum MatchEvent {
     BallLaunched,
     BallGrabbed,
     BallLost,
-    BlockLost,
+    // Tag of the lost block
+    BlockLost(Option<String>),
     BounceOffPaddle,
     BounceOffWall,
-    BlockHit(Vec3, BlockType, BlockBehaviour),
+    // Position, type, behaviour and tag of the destroyed block
+    BlockHit(Vec3, BlockType, BlockBehaviour, Option<String>),
+    // Position and radius of an explosive block going off
+    BlockExploded(Vec3, f32),
     PickedUp(PickupType),
 }
 
@@ -60,7 +67,7 @@ impl Plugin for EventsPlugin {
 }
 
 
-#[derive(PartialEq)]
+#[derive(Debug, PartialEq)]
 enum LevelEndState {
     Won,
     Lost,
@@ -74,21 +81,13 @@ fn check_win_criteria(
     stats: &MatchState,
 ) -> LevelEndState {
 
-    if stats.blocks == 0 {
-        match win_criteria {
-            WinCriteria::BlockHitPercentage(pct) => {
-
-                let result = (stats.blocks_hit as f32) / (stats.blocks_hit as f32 + stats.blocks_lost as f32);
-                return if result >= *pct {
-                    LevelEndState::Won
-                } else {
-                    LevelEndState::Lost
-                }
-            }
-        };
+    match win_criteria.evaluate(stats) {
+        CriteriaState::Met => return LevelEndState::Won,
+        CriteriaState::Failed => return LevelEndState::Lost,
+        CriteriaState::Open => {}
     }
 
-    if player.total_ball_count() == 0 && stats.blocks > 0 {
+    if player.total_ball_count() == 0 {
         return LevelEndState::Lost;
     }
 
@@ -147,17 +146,25 @@ fn match_event_handler(
                 match_state.add_wall_hit();
             }
 
-            MatchEvent::BlockHit(p, block_type, behaviour) => {
+            MatchEvent::BlockHit(p, block_type, behaviour, tag) => {
                 let (_, awarded) = match_state.add_block_hit(block_type, behaviour);
 
+                if let Some(tag) = tag {
+                    match_state.tagged_block_destroyed(tag);
+                }
+
                 commands.spawn(PointsDisplay {
                     text: awarded.to_string(),
                     position: p.clone(),
                 }).insert(PointsDisplayRequest);
             }
 
-            MatchEvent::BlockLost => {
+            MatchEvent::BlockLost(tag) => {
                 match_state.block_lost();
+
+                if let Some(tag) = tag {
+                    match_state.tagged_block_lost(tag);
+                }
             }
 
             MatchEvent::BallGrabbed => {
@@ -173,20 +180,24 @@ fn match_event_handler(
 
                 //info!("Player picked up {:?}", pt)
             }
-        }
 
-        match check_win_criteria(&level.win_criteria, &player, &match_state) {
-            LevelEndState::Won => game_flow.send(GameFlowEvent::PlayerWins),
-            LevelEndState::Lost => game_flow.send(GameFlowEvent::PlayerLooses),
-            LevelEndState::Undecided => {}
+            MatchEvent::BlockExploded(_, _) => {}
         }
     }
+
+    // Checked every frame, as some criteria are met just by time passing
+    match check_win_criteria(&level.win_criteria, &player, &match_state) {
+        LevelEndState::Won => game_flow.send(GameFlowEvent::PlayerWins),
+        LevelEndState::Lost => game_flow.send(GameFlowEvent::PlayerLooses),
+        LevelEndState::Undecided => {}
+    }
 }
 
 fn game_flow_handler(
     mut players: Query<&mut Player>,
     mut events: EventReader<GameFlowEvent>,
     mut match_state: ResMut<MatchState>,
+    levels: Res<Levels>,
     mut game_state: ResMut<State<GameState>>,
 ) {
     for ev in events.iter() {
@@ -199,10 +210,19 @@ fn game_flow_handler(
                 let _ = game_state.set(GameState::InMatch);
             }
 
+            // The match keeps sending its outcome until the state has changed, only the first one counts
             GameFlowEvent::PlayerWins => {
                 if let Ok(mut player) = players.get_single_mut() {
+                    if !matches!(player.state, PlayerState::Open) {
+                        continue;
+                    }
+
                     //info!("Player wins!");
                     player.state = PlayerState::HasWon;
+
+                    let time_limit = levels.get_current_level().and_then(|level| level.time_limit);
+                    match_state.points += match_state.time_bonus(time_limit);
+
                     player.player_has_won(match_state.points);
                     //info!("Player now has {} points", player.points);
                     let _ = game_state.set(GameState::PostMatch);
@@ -215,12 +235,20 @@ fn game_flow_handler(
 
             GameFlowEvent::PlayerLooses => {
                 if let Ok(mut player) = players.get_single_mut() {
+                    if !matches!(player.state, PlayerState::Open) {
+                        continue;
+                    }
+
                     //info!("Player looses!");
                     player.state = PlayerState::HasLost;
                     let _ = game_state.set(GameState::PostMatch);
                 };
             }
 
+            GameFlowEvent::OpenEditor => {
+                let _ = game_state.set(GameState::Editor);
+            }
+
             GameFlowEvent::EndGame => {}
         }
     };
@@ -229,7 +257,7 @@ fn game_flow_handler(
 
 #[cfg(test)]
 mod tests {
-    use super::check_win_criteria;
+    use super::{check_win_criteria, LevelEndState};
 
     use bevy::utils::default;
     use crate::level::WinCriteria;
@@ -253,6 +281,6 @@ mod tests {
 
         let crit = WinCriteria::BlockHitPercentage(1.0);
 
-        assert_eq!(check_win_criteria(&crit, &player, &stats), false);
+        assert_eq!(check_win_criteria(&crit, &player, &stats), LevelEndState::Undecided);
     }
 }
\ No newline at end of file
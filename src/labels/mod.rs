@@ -4,5 +4,8 @@ use bevy::prelude::SystemLabel;
 #[derive(SystemLabel)]
 pub enum SystemLabels {
     UpdateWorld,
-    UpdateState
+    UpdateState,
+    LevelReload,
+    // Blocks destroyed by explosions, their hits have to be seen by the pickups in the same frame
+    BlockExplosions,
 }
\ No newline at end of file
@@ -0,0 +1,550 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bevy::log::warn;
+
+use ron::ser::PrettyConfig;
+use serde::Deserialize;
+
+use crate::block::Block;
+use crate::level::layout::{interpret_freeform, interpret_grid, interpret_hex_layout, interpret_layout, interpret_rings, Layout, LayoutWarning};
+use crate::level::{LevelDefinition, LevelObstacle, Levels, TargetLayout};
+use crate::level::custom::CustomLayouts;
+use crate::config::MAX_LAYER;
+
+
+/*
+    A campaign file lists the level files that make up the campaign, in order.
+    Paths are relative to the campaign file.
+
+    (
+        levels: [
+            "level0.ron",
+            "level1.ron",
+        ],
+    )
+ */
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CampaignDefinition {
+    levels: Vec<String>,
+}
+
+
+#[derive(Debug)]
+pub struct CampaignError {
+    pub file: PathBuf,
+    pub level: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for CampaignError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.level {
+            None => write!(f, "{}: {}", self.file.display(), self.message),
+            Some(level) => write!(f, "{} (level {}): {}", self.file.display(), level, self.message),
+        }
+    }
+}
+
+impl std::error::Error for CampaignError {}
+
+
+pub fn load_campaign(path: &Path, layouts: &CustomLayouts) -> Result<Levels, CampaignError> {
+    let source = fs::read_to_string(path).map_err(|e| CampaignError {
+        file: path.to_path_buf(),
+        level: None,
+        message: e.to_string(),
+    })?;
+
+    let campaign: CampaignDefinition = ron::from_str(&source).map_err(|e| CampaignError {
+        file: path.to_path_buf(),
+        level: None,
+        message: e.to_string(),
+    })?;
+
+    if campaign.levels.is_empty() {
+        return Err(CampaignError {
+            file: path.to_path_buf(),
+            level: None,
+            message: "campaign has no levels".to_string(),
+        });
+    }
+
+    let base = path.parent().unwrap_or(Path::new(""));
+    let mut definitions = vec![];
+    let mut sources = vec![];
+
+    for (index, file) in campaign.levels.iter().enumerate() {
+        let source = base.join(file);
+        definitions.push(load_level(&source, index, layouts)?);
+        sources.push(source);
+    }
+
+    Ok(Levels {
+        definitions,
+        sources,
+        current_level: 0,
+    })
+}
+
+pub fn load_level(path: &Path, index: usize, layouts: &CustomLayouts) -> Result<LevelDefinition, CampaignError> {
+    let level = fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|source| parse_level(&source))
+        .and_then(|level| check_custom_layout(&level, layouts).map(|_| level))
+        .map_err(|message| CampaignError {
+            file: path.to_path_buf(),
+            level: Some(index),
+            message,
+        })?;
+
+    for warning in level_warnings(&level) {
+        warn!("{} (level {}): SparseGrid layout, {}", path.display(), index, warning);
+    }
+
+    Ok(level)
+}
+
+// Used by the editor. Comments in the file are not preserved.
+pub fn save_level(path: &Path, level: &LevelDefinition) -> Result<(), CampaignError> {
+    let error = |message: String| CampaignError {
+        file: path.to_path_buf(),
+        level: None,
+        message,
+    };
+
+    validate_level(level).map_err(error)?;
+
+    let source = ron::ser::to_string_pretty(level, PrettyConfig::default())
+        .map_err(|e| error(e.to_string()))?;
+
+    fs::write(path, source).map_err(|e| error(e.to_string()))
+}
+
+pub fn parse_level(source: &str) -> Result<LevelDefinition, String> {
+    let level: LevelDefinition = ron::from_str(source).map_err(|e| e.to_string())?;
+    validate_level(&level)?;
+
+    Ok(level)
+}
+
+
+// Custom layouts are registered by plugins, so they are checked apart from parsing
+pub fn check_custom_layout(level: &LevelDefinition, layouts: &CustomLayouts) -> Result<(), String> {
+    match &level.targets {
+        TargetLayout::Custom(name) if !layouts.contains(name) => {
+            Err(format!("unknown Custom layout `{}`, known layouts are {}", name, layouts.names().join(", ")))
+        }
+
+        _ => Ok(())
+    }
+}
+
+
+// Problems that do not stop the level from loading. Group names are shared by all layers, so a
+// name is only used once if no other layer uses it either.
+pub fn level_warnings(level: &LevelDefinition) -> Vec<LayoutWarning> {
+    let layouts: Vec<Layout> = std::iter::once(&level.targets)
+        .chain(level.layers.iter().map(|layer| &layer.targets))
+        .filter_map(|targets| match targets {
+            TargetLayout::SparseGrid(layout, gap) => interpret_layout(layout, *gap, false).ok(),
+            TargetLayout::HexGrid(layout, gap) => interpret_hex_layout(layout, *gap, false).ok(),
+            TargetLayout::Radial(center, rings) => interpret_rings(*center, rings).ok(),
+            TargetLayout::Freeform(placements) => interpret_freeform(placements).ok(),
+            _ => None,
+        })
+        .collect();
+
+    let used_elsewhere = |i: usize, group: &str| layouts
+        .iter()
+        .enumerate()
+        .any(|(j, other)| j != i && other.names.iter().any(|(_, name)| name == group));
+
+    layouts
+        .iter()
+        .enumerate()
+        .flat_map(|(i, layout)| layout.warnings
+            .iter()
+            .filter(move |w| !used_elsewhere(i, &w.group))
+            .cloned())
+        .collect()
+}
+
+
+fn check_tags(level: &LevelDefinition, blocks: &[Block]) -> Result<(), String> {
+    for tag in level.win_criteria.tags() {
+        if !blocks.iter().any(|b| b.tag.as_deref() == Some(tag)) {
+            return Err(format!("win criteria need blocks tagged `{}`, the layout has none", tag));
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_targets(targets: &TargetLayout) -> Result<Vec<Block>, String> {
+    match targets {
+        TargetLayout::FilledGrid(cols, rows, _, _, gap) => {
+            if *cols == 0 || *rows == 0 {
+                return Err(format!("FilledGrid needs at least one row and column, got {}x{}", cols, rows));
+            }
+
+            if *gap < 0.0 {
+                return Err(format!("FilledGrid gap must not be negative, got {}", gap));
+            }
+
+            // Filled grids have no tags
+            Ok(vec![])
+        }
+
+        TargetLayout::SparseGrid(layout, gap) => {
+            if *gap < 0.0 {
+                return Err(format!("SparseGrid gap must not be negative, got {}", gap));
+            }
+
+            interpret_grid(layout, *gap, false).map_err(|e| format!("SparseGrid layout, {}", e))
+        }
+
+        TargetLayout::HexGrid(layout, gap) => {
+            if *gap < 0.0 {
+                return Err(format!("HexGrid gap must not be negative, got {}", gap));
+            }
+
+            interpret_hex_layout(layout, *gap, false)
+                .map(|layout| layout.blocks)
+                .map_err(|e| format!("HexGrid layout, {}", e))
+        }
+
+        TargetLayout::Radial(center, rings) => {
+            if rings.is_empty() {
+                return Err("Radial layout needs at least one ring".to_string());
+            }
+
+            for (i, ring) in rings.iter().enumerate() {
+                if ring.radius <= 0.0 || ring.count == 0 {
+                    return Err(format!("ring {}: needs a positive radius and at least one block", i));
+                }
+            }
+
+            interpret_rings(*center, rings)
+                .map(|layout| layout.blocks)
+                .map_err(|e| format!("Radial layout, {}", e))
+        }
+
+        TargetLayout::Freeform(placements) => {
+            if placements.is_empty() {
+                return Err("Freeform layout needs at least one block".to_string());
+            }
+
+            interpret_freeform(placements)
+                .map(|layout| layout.blocks)
+                .map_err(|e| format!("Freeform layout, {}", e))
+        }
+
+        TargetLayout::Custom(name) => {
+            if name.is_empty() {
+                return Err("Custom layout needs a name".to_string());
+            }
+
+            // Blocks of custom layouts are only known once they are built
+            Ok(vec![])
+        }
+    }
+}
+
+fn validate_level(level: &LevelDefinition) -> Result<(), String> {
+    if level.background_asset.is_empty() {
+        return Err("background_asset must not be empty".to_string());
+    }
+
+    if level.simultaneous_balls < 1 {
+        return Err(format!("simultaneous_balls must be at least 1, got {}", level.simultaneous_balls));
+    }
+
+    level.win_criteria.validate()?;
+
+    let mut blocks = validate_targets(&level.targets)?;
+    let custom = matches!(level.targets, TargetLayout::Custom(_));
+
+    for (i, layer) in level.layers.iter().enumerate() {
+        if layer.layer == 0 || layer.layer.abs() > MAX_LAYER {
+            return Err(format!("layer {}: must be between -{} and {} but not 0, got {}", i, MAX_LAYER, MAX_LAYER, layer.layer));
+        }
+
+        if level.layers[..i].iter().any(|other| other.layer == layer.layer) {
+            return Err(format!("layer {}: layer {} is listed twice", i, layer.layer));
+        }
+
+        if custom || matches!(layer.targets, TargetLayout::Custom(_)) {
+            return Err(format!("layer {}: Custom layouts can not be stacked in layers", i));
+        }
+
+        blocks.extend(validate_targets(&layer.targets).map_err(|e| format!("layer {}: {}", i, e))?);
+    }
+
+    if !custom {
+        check_tags(level, &blocks)?;
+    }
+
+    for (i, lane) in level.conveyor.iter().enumerate() {
+        if lane.speed <= 0.0 || lane.interval <= 0.0 || lane.delay < 0.0 {
+            return Err(format!("conveyor lane {}: speed and interval must be positive and delay must not be negative", i));
+        }
+
+        if lane.rows == 0 || lane.blocks_per_row == 0 {
+            return Err(format!("conveyor lane {}: needs at least one row and one block per row", i));
+        }
+    }
+
+    for (i, logic) in level.triggers.iter().enumerate() {
+        logic.validate().map_err(|e| format!("trigger {}: {}", i, e))?;
+    }
+
+    for (i, obstacle) in level.obstacles.iter().enumerate() {
+        match obstacle {
+            LevelObstacle::Box(_, w, h) => {
+                if *w <= 0.0 || *h <= 0.0 {
+                    return Err(format!("obstacle {}: Box needs a positive width and height, got {}x{}", i, w, h));
+                }
+            }
+
+            LevelObstacle::PathBox(waypoints, w, h, speed, _) => {
+                if waypoints.len() < 2 {
+                    return Err(format!("obstacle {}: PathBox needs at least 2 waypoints, got {}", i, waypoints.len()));
+                }
+
+                if *w <= 0.0 || *h <= 0.0 || *speed <= 0.0 {
+                    return Err(format!("obstacle {}: PathBox needs a positive width, height and speed", i));
+                }
+            }
+
+            LevelObstacle::RotatingBox(_, _, w, h, speed, _) => {
+                if *w <= 0.0 || *h <= 0.0 || !speed.is_finite() {
+                    return Err(format!("obstacle {}: RotatingBox needs a positive width and height and a finite speed", i));
+                }
+            }
+
+            LevelObstacle::Lift(_, w, h, a, b) => {
+                if *w <= 0.0 || *h <= 0.0 {
+                    return Err(format!("obstacle {}: Lift needs a positive width and depth, got {}x{}", i, w, h));
+                }
+
+                if a == b || a.abs() > MAX_LAYER || b.abs() > MAX_LAYER {
+                    return Err(format!("obstacle {}: Lift needs two different layers between -{} and {}, got {} and {}", i, MAX_LAYER, MAX_LAYER, a, b));
+                }
+            }
+
+            LevelObstacle::ForceField(_, normal, size, _) |
+            LevelObstacle::DirectionalDeathTrigger(_, normal, size) => {
+                if normal.length() == 0.0 {
+                    return Err(format!("obstacle {}: normal must not be zero", i));
+                }
+
+                if *size <= 0.0 {
+                    return Err(format!("obstacle {}: size must be positive, got {}", i, size));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+
+// Time values in level files are given in seconds
+pub mod optional_seconds {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde::de::Error;
+
+    pub fn serialize<S: Serializer>(value: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+        value.map(|d| d.as_secs_f32()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+        match Option::<f32>::deserialize(deserializer)? {
+            None => Ok(None),
+            Some(secs) if secs.is_finite() && secs > 0.0 => Ok(Some(Duration::from_secs_f32(secs))),
+            Some(secs) => Err(D::Error::custom(format!("expected a positive number of seconds, got {}", secs))),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_level() {
+        let level = parse_level(r#"(
+            simultaneous_balls: 2,
+            targets: SparseGrid("AA AA
+ AB AA", 2.0),
+            time_limit: Some(90.0),
+            global_pickups: [MoreBalls(1), Grabber(2)],
+            obstacles: [
+                Box((0.0, 0.0, -70.0), 15.0, 200.0),
+            ],
+        )"#).unwrap();
+
+        assert_eq!(level.simultaneous_balls, 2);
+        assert_eq!(level.global_pickups.len(), 2);
+        assert_eq!(level.obstacles.len(), 1);
+        assert_eq!(level.time_limit.unwrap().as_secs(), 90);
+        assert!(level.default_wall_l);
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        let err = parse_level("(simultaneous_ballz: 2)").err().unwrap();
+
+        assert!(err.contains("simultaneous_ballz"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        assert!(parse_level("(simultaneous_balls: 0)").is_err());
+        assert!(parse_level("(win_criteria: BlockHitPercentage(1.5))").is_err());
+        assert!(parse_level("(time_limit: Some(-3.0))").is_err());
+        assert!(parse_level("(obstacles: [Box((0.0, 0.0, 0.0), 0.0, 10.0)])").is_err());
+        assert!(parse_level("(conveyor: [(interval: 0.0)])").is_err());
+        assert!(parse_level("(obstacles: [PathBox([(0.0, 0.0, 0.0)], 10.0, 10.0, 5.0, None)])").is_err());
+        assert!(parse_level("(obstacles: [PathBox([(0.0, 0.0, 0.0), (50.0, 0.0, 0.0)], 10.0, 10.0, 5.0, Some(1))])").is_ok());
+    }
+
+    #[test]
+    fn checks_tags_of_win_criteria() {
+        let level = |criteria: &str, layout: &str| parse_level(&format!(
+            r#"(win_criteria: {}, targets: SparseGrid("{}", 2.0))"#, criteria, layout
+        ));
+
+        assert!(level(r#"All([DestroyTagged("core"), DeathZone(0)])"#, "AA AA{tag=core} AA").is_ok());
+        assert!(level(r#"Any([Points(500), DestroyTagged("core")])"#, "AA AA AA").is_err());
+        assert!(level(r#"DestroyTagged("core")"#, "AA").unwrap_err().contains("tagged `core`"));
+    }
+
+    #[test]
+    fn parses_radial_and_freeform_layouts() {
+        let radial = parse_level(r#"(
+            win_criteria: DestroyTagged("core"),
+            targets: Radial((0.0, -50.0), [
+                (radius: 20.0, count: 6, slot: "BA"),
+                (radius: 40.0, count: 12, rotation: 15.0, slot: "AA{tag=core}"),
+            ]),
+        )"#);
+        assert!(radial.is_ok(), "{:?}", radial.err());
+
+        assert!(parse_level(r#"(targets: Radial((0.0, -50.0), [(radius: 0.0)]))"#).is_err());
+        assert!(parse_level(r#"(targets: Radial((0.0, -50.0), [(slot: "QA")]))"#).is_err());
+        assert!(parse_level(r#"(targets: Freeform([]))"#).is_err());
+        assert!(parse_level(r#"(targets: Freeform([(position: (5.0, -40.0), rotation: 30.0, slot: "DA")]))"#).is_ok());
+        assert!(parse_level(r#"(targets: HexGrid("AA AA\nAA AA", 2.0))"#).is_ok());
+    }
+
+    #[test]
+    fn stacks_block_layers() {
+        let layered = |layers: &str| parse_level(&format!(
+            r#"(win_criteria: DestroyTagged("top"), targets: SparseGrid("AA AA", 2.0), layers: [{}])"#, layers
+        ));
+
+        assert!(layered(r#"(layer: 1, targets: SparseGrid("BA{tag=top}", 2.0))"#).is_ok());
+        assert!(layered(r#"(layer: 1, targets: SparseGrid("BA", 2.0))"#).is_err());
+        assert!(layered(r#"(layer: 0, targets: SparseGrid("BA{tag=top}", 2.0))"#).is_err());
+        assert!(layered(r#"(layer: 1, targets: SparseGrid("BA{tag=top}", 2.0)), (layer: 1, targets: SparseGrid("AA", 2.0))"#).is_err());
+        assert!(parse_level(r#"(targets: Custom("Conveyor"), layers: [(layer: -1, targets: SparseGrid("AA", 2.0))])"#).is_err());
+
+        assert!(parse_level("(obstacles: [Lift((0.0, 0.0, -20.0), 20.0, 10.0, 0, 1)])").is_ok());
+        assert!(parse_level("(obstacles: [Lift((0.0, 0.0, -20.0), 20.0, 10.0, 1, 1)])").is_err());
+        assert!(parse_level("(obstacles: [Lift((0.0, 0.0, -20.0), 20.0, 10.0, 0, 2)])").is_err());
+
+        // A group named in two layers is not used only once
+        let level = parse_level(r#"(
+            targets: SparseGrid("ZIR[door] AA", 2.0),
+            layers: [(layer: 1, targets: SparseGrid("AAA[door] BAA[lift]", 2.0))],
+        )"#).unwrap();
+        let warnings = level_warnings(&level);
+        assert_eq!(warnings.iter().map(|w| w.group.as_str()).collect::<Vec<_>>(), vec!["lift"]);
+    }
+
+    #[test]
+    fn reads_trigger_logic() {
+        let level = parse_level("(triggers: [All([1, 2], 3), Counter(4, 3, 5), Timed(3, 2.5)])").unwrap();
+        assert_eq!(level.triggers.len(), 3);
+
+        assert!(parse_level("(triggers: [Any([], 3)])").is_err());
+        assert!(parse_level("(triggers: [Timed(3, -1.0)])").is_err());
+    }
+
+    #[test]
+    fn reports_layout_typos() {
+        let err = parse_level(r#"(targets: SparseGrid("AA AA QA", 2.0))"#).err().unwrap();
+
+        assert!(err.contains("line 1, column 7"), "{}", err);
+    }
+
+    #[test]
+    fn loads_the_shipped_campaign() {
+        let mut layouts = CustomLayouts::default();
+        layouts.register("Conveyor", crate::level::conveyor::level_spawn_conveyor);
+
+        let levels = load_campaign(Path::new(crate::config::CAMPAIGN_FILE), &layouts).unwrap();
+
+        for level in &levels.definitions {
+            if let TargetLayout::SparseGrid(layout, gap) = &level.targets {
+                assert!(interpret_grid(layout, *gap, true).is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn saved_levels_load_again() {
+        let level = parse_level(r#"(
+            targets: SparseGrid("AA ..
+ .. BCA3", 2.0),
+            time_limit: Some(30.0),
+            obstacles: [
+                ForceField((0.0, 0.0, -20.0), (0.0, 0.0, 1.0), 45.0, false),
+            ],
+        )"#).unwrap();
+
+        let path = std::env::temp_dir().join("saved_levels_load_again.ron");
+        save_level(&path, &level).unwrap();
+        let loaded = load_level(&path, 0, &CustomLayouts::default()).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert!(matches!(&loaded.targets, TargetLayout::SparseGrid(layout, _) if layout.contains("BCA3")));
+        assert_eq!(loaded.time_limit, level.time_limit);
+        assert_eq!(loaded.obstacles.len(), 1);
+    }
+
+    #[test]
+    fn rejects_unknown_custom_layouts() {
+        fn no_blocks(_: &mut bevy::prelude::Commands, _: &mut LevelDefinition, _: &mut crate::r#match::state::MatchState) -> i32 {
+            0
+        }
+
+        let mut layouts = CustomLayouts::default();
+        layouts.register("Conveyor", no_blocks);
+
+        let level = parse_level(r#"(targets: Custom("Conveyor"))"#).unwrap();
+        assert!(check_custom_layout(&level, &layouts).is_ok());
+
+        let level = parse_level(r#"(targets: Custom("Conveyer"))"#).unwrap();
+        let err = check_custom_layout(&level, &layouts).err().unwrap();
+        assert!(err.contains("Conveyer") && err.contains("Conveyor"), "{}", err);
+    }
+
+    #[test]
+    fn names_file_and_level() {
+        let err = CampaignError {
+            file: PathBuf::from("assets/levels/level3.ron"),
+            level: Some(3),
+            message: "boom".to_string(),
+        };
+
+        assert_eq!(err.to_string(), "assets/levels/level3.ron (level 3): boom");
+    }
+}
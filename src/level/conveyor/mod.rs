@@ -0,0 +1,246 @@
+use bevy::app::{App, Plugin};
+use bevy::math::Vec2;
+use bevy::prelude::{Commands, Component, DespawnRecursiveExt, Entity, EventReader, IntoSystemDescriptor, Query, Res, SystemSet, Time, With};
+use bevy::utils::default;
+use serde::{Deserialize, Serialize};
+
+use crate::block::{Block, BlockBehaviour, BlockType};
+use crate::config::{ARENA_WIDTH_H, BLOCK_GAP, BLOCK_WIDTH};
+use crate::labels::SystemLabels;
+use crate::level::{LevelDefinition, LevelReloaded, RequestTag};
+use crate::level::custom::RegisterCustomLayout;
+use crate::r#match::state::MatchState;
+use crate::state::GameState;
+
+
+/*
+    The Conveyor layout sends rows of evaders through the arena along lanes. Each lane emits
+    its rows at an interval for as long as the match runs or until its rows are used up.
+
+    Every block the lanes will ever spawn is counted up front, so MatchState::blocks includes
+    the blocks that are still to come and the level is decided once the last row is gone.
+    Blocks leaving through a DirectionalDeathTrigger count as lost.
+
+        targets: Custom("Conveyor"),
+        conveyor: [
+            (z: -25.0, speed: 10.0, interval: 6.0, rows: 5),
+            (z: -35.0, from_left: true, speed: 15.0, delay: 3.0, interval: 6.0, rows: 5),
+        ],
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConveyorLane {
+    // Depth of the lane
+    pub z: f32,
+    // Blocks enter on the right side and move left, unless this is set
+    pub from_left: bool,
+    pub speed: f32,
+    // Seconds until the first row
+    pub delay: f32,
+    // Seconds between rows
+    pub interval: f32,
+    pub rows: usize,
+    pub blocks_per_row: usize,
+    pub block_type: BlockType,
+}
+
+impl Default for ConveyorLane {
+    fn default() -> Self {
+        ConveyorLane {
+            z: -25.0,
+            from_left: false,
+            speed: 10.0,
+            delay: 0.0,
+            interval: 8.0,
+            rows: 1,
+            blocks_per_row: 2,
+            block_type: BlockType::Simple,
+        }
+    }
+}
+
+impl ConveyorLane {
+    fn block_count(&self) -> i32 {
+        match self.block_type {
+            BlockType::Obstacle => 0,
+            _ => (self.rows * self.blocks_per_row) as i32,
+        }
+    }
+}
+
+// Used by levels that do not define their lanes
+fn default_lanes() -> Vec<ConveyorLane> {
+    vec![
+        ConveyorLane {
+            z: -25.0,
+            ..default()
+        },
+        ConveyorLane {
+            z: -35.0,
+            from_left: true,
+            ..default()
+        },
+    ]
+}
+
+
+struct LaneState {
+    lane: ConveyorLane,
+    // Seconds until the next row
+    next_row: f32,
+    rows_left: usize,
+}
+
+#[derive(Component)]
+pub struct ConveyorSpawner {
+    lanes: Vec<LaneState>,
+}
+
+
+pub struct ConveyorPlugin;
+
+impl Plugin for ConveyorPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .register_custom_layout("Conveyor", level_spawn_conveyor)
+
+            .add_system_set(
+                SystemSet::on_update(GameState::InMatch)
+                    .with_system(conveyor_spawn_rows.label(SystemLabels::UpdateWorld))
+                    .with_system(conveyor_despawn_on_reload.after(SystemLabels::LevelReload))
+            )
+
+            .add_system_set(
+                SystemSet::on_exit(GameState::PostMatch)
+                    .with_system(conveyor_despawn)
+            )
+        ;
+    }
+}
+
+
+pub(crate) fn level_spawn_conveyor(
+    commands: &mut Commands,
+    level: &mut LevelDefinition,
+    stats: &mut MatchState,
+) -> i32 {
+    let lanes = if level.conveyor.is_empty() {
+        default_lanes()
+    } else {
+        level.conveyor.clone()
+    };
+
+    let count = lanes.iter().map(|lane| lane.block_count()).sum();
+
+    commands.spawn(ConveyorSpawner {
+        lanes: lanes
+            .into_iter()
+            .map(|lane| LaneState {
+                next_row: lane.delay,
+                rows_left: lane.rows,
+                lane,
+            })
+            .collect(),
+    });
+
+    level.distribute_global_pickups(count as usize, stats.seed);
+    count
+}
+
+
+fn conveyor_spawn_rows(
+    mut commands: Commands,
+    mut spawners: Query<&mut ConveyorSpawner>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+
+    for mut spawner in &mut spawners {
+        for state in &mut spawner.lanes {
+            state.next_row -= dt;
+
+            while state.rows_left > 0 && state.next_row <= 0.0 {
+                spawn_row(&mut commands, &state.lane);
+
+                state.rows_left -= 1;
+                state.next_row += state.lane.interval;
+            }
+        }
+    }
+}
+
+fn spawn_row(commands: &mut Commands, lane: &ConveyorLane) {
+    // Rows start just outside the arena, later blocks of a row queue up behind the first one
+    let (mut pos, step, behaviour) = if lane.from_left {
+        (Vec2::new(-ARENA_WIDTH_H - 3.0, lane.z), -(2.0 * BLOCK_WIDTH + BLOCK_GAP), BlockBehaviour::EvaderR(lane.speed))
+    } else {
+        (Vec2::new(ARENA_WIDTH_H + 3.0, lane.z), 2.0 * BLOCK_WIDTH + BLOCK_GAP, BlockBehaviour::EvaderL(lane.speed))
+    };
+
+    for _ in 0..lane.blocks_per_row {
+        commands
+            .spawn(Block {
+                position: pos,
+                behaviour: behaviour.clone(),
+                block_type: lane.block_type.clone(),
+                ..default()
+            })
+            .insert(RequestTag);
+
+        pos.x += step;
+    }
+}
+
+
+fn conveyor_despawn(
+    mut commands: Commands,
+    spawners: Query<Entity, With<ConveyorSpawner>>,
+) {
+    for spawner in &spawners {
+        commands.entity(spawner)
+            .despawn_recursive();
+    }
+}
+
+fn conveyor_despawn_on_reload(
+    commands: Commands,
+    mut events: EventReader<LevelReloaded>,
+    spawners: Query<Entity, With<ConveyorSpawner>>,
+) {
+    if events.iter().count() == 0 {
+        return;
+    }
+
+    conveyor_despawn(commands, spawners);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_the_blocks_to_come() {
+        let lanes = vec![
+            ConveyorLane {
+                rows: 5,
+                blocks_per_row: 3,
+                ..default()
+            },
+            ConveyorLane {
+                rows: 4,
+                block_type: BlockType::Obstacle,
+                ..default()
+            },
+            ConveyorLane {
+                rows: 2,
+                blocks_per_row: 1,
+                block_type: BlockType::Hardling,
+                ..default()
+            },
+        ];
+
+        assert_eq!(lanes.iter().map(|lane| lane.block_count()).sum::<i32>(), 17);
+        assert_eq!(default_lanes().iter().map(|lane| lane.block_count()).sum::<i32>(), 4);
+    }
+}
@@ -0,0 +1,351 @@
+use serde::{Deserialize, Serialize};
+
+use crate::r#match::state::MatchState;
+
+
+/*
+    What the player has to do to win a level. Criteria can be combined into compound objectives:
+
+        win_criteria: All([DestroyTagged("core"), DeathZone(0)]),
+        win_criteria: Any([Points(5000), Survive(90.0)]),
+
+    Loosing the last ball looses the level whatever the criteria say.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum WinCriteria {
+    // Fraction of the blocks that has to be hit rather than lost, decided once no blocks are left
+    BlockHitPercentage(f32),
+    // Points of the match, failed when no blocks are left to score with
+    Points(i32),
+    // Seconds on the match clock, which only runs while a ball is in play
+    Survive(f32),
+    // Every block with the tag has to be hit, loosing one of them looses the level
+    DestroyTagged(String),
+    // Number of blocks that may reach a death trigger. On its own it is met by clearing the level,
+    // inside All it only has to hold until the other criteria are met.
+    DeathZone(i32),
+    All(Vec<WinCriteria>),
+    Any(Vec<WinCriteria>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CriteriaState {
+    Met,
+    Failed,
+    Open,
+}
+
+
+impl WinCriteria {
+    pub fn evaluate(&self, stats: &MatchState) -> CriteriaState {
+        match self {
+            WinCriteria::BlockHitPercentage(pct) => {
+                if stats.blocks > 0 {
+                    return CriteriaState::Open;
+                }
+
+                let done = stats.blocks_hit + stats.blocks_lost;
+                if done == 0 || stats.blocks_hit as f32 / done as f32 >= *pct {
+                    CriteriaState::Met
+                } else {
+                    CriteriaState::Failed
+                }
+            }
+
+            WinCriteria::Points(points) => {
+                if stats.points >= *points {
+                    CriteriaState::Met
+                } else if stats.blocks <= 0 {
+                    CriteriaState::Failed
+                } else {
+                    CriteriaState::Open
+                }
+            }
+
+            WinCriteria::Survive(secs) => {
+                if stats.time_taken.as_secs_f32() >= *secs {
+                    CriteriaState::Met
+                } else {
+                    CriteriaState::Open
+                }
+            }
+
+            WinCriteria::DestroyTagged(tag) => match stats.tagged.get(tag) {
+                Some(tagged) if tagged.lost > 0 => CriteriaState::Failed,
+                Some(tagged) if tagged.destroyed >= tagged.total => CriteriaState::Met,
+                _ => CriteriaState::Open,
+            },
+
+            WinCriteria::DeathZone(allowed) => {
+                if stats.blocks_lost > *allowed {
+                    CriteriaState::Failed
+                } else if stats.blocks <= 0 {
+                    CriteriaState::Met
+                } else {
+                    CriteriaState::Open
+                }
+            }
+
+            WinCriteria::All(criteria) => {
+                let states: Vec<CriteriaState> = criteria.iter().map(|c| c.evaluate(stats)).collect();
+
+                if states.contains(&CriteriaState::Failed) {
+                    return CriteriaState::Failed;
+                }
+
+                // Guards that have not failed yet do not hold the level back
+                let goals: Vec<&CriteriaState> = criteria
+                    .iter()
+                    .zip(&states)
+                    .filter(|(c, _)| !c.is_guard())
+                    .map(|(_, s)| s)
+                    .collect();
+
+                let met = if goals.is_empty() {
+                    states.iter().all(|s| *s == CriteriaState::Met)
+                } else {
+                    goals.iter().all(|s| **s == CriteriaState::Met)
+                };
+
+                if met { CriteriaState::Met } else { CriteriaState::Open }
+            }
+
+            WinCriteria::Any(criteria) => {
+                let states: Vec<CriteriaState> = criteria.iter().map(|c| c.evaluate(stats)).collect();
+
+                if states.contains(&CriteriaState::Met) {
+                    CriteriaState::Met
+                } else if !states.is_empty() && states.iter().all(|s| *s == CriteriaState::Failed) {
+                    CriteriaState::Failed
+                } else {
+                    CriteriaState::Open
+                }
+            }
+        }
+    }
+
+    // How far the player got, from 0.0 to 1.0
+    pub fn progress(&self, stats: &MatchState) -> f32 {
+        let fraction = |a: f32, b: f32| if b > 0.0 { (a / b).clamp(0.0, 1.0) } else { 1.0 };
+
+        match self {
+            WinCriteria::BlockHitPercentage(pct) => {
+                let total = stats.blocks_hit + stats.blocks_lost + stats.blocks.max(0);
+                fraction(stats.blocks_hit as f32, pct * total as f32)
+            }
+
+            WinCriteria::Points(points) => fraction(stats.points as f32, *points as f32),
+
+            WinCriteria::Survive(secs) => fraction(stats.time_taken.as_secs_f32(), *secs),
+
+            WinCriteria::DestroyTagged(tag) => match stats.tagged.get(tag) {
+                Some(tagged) => fraction(tagged.destroyed as f32, tagged.total as f32),
+                None => 0.0,
+            },
+
+            WinCriteria::DeathZone(_) => match self.evaluate(stats) {
+                CriteriaState::Failed => 0.0,
+                _ => 1.0,
+            },
+
+            WinCriteria::All(criteria) => {
+                if criteria.is_empty() {
+                    return 1.0;
+                }
+
+                criteria.iter().map(|c| c.progress(stats)).sum::<f32>() / criteria.len() as f32
+            }
+
+            WinCriteria::Any(criteria) => criteria
+                .iter()
+                .map(|c| c.progress(stats))
+                .fold(0.0, f32::max),
+        }
+    }
+
+    // Short text for the HUD
+    pub fn describe(&self, stats: &MatchState) -> String {
+        match self {
+            WinCriteria::BlockHitPercentage(pct) => {
+                let total = stats.blocks_hit + stats.blocks_lost + stats.blocks.max(0);
+                format!("Blocks {}/{}", stats.blocks_hit, (pct * total as f32).ceil() as i32)
+            }
+
+            WinCriteria::Points(points) => format!("Points {}/{}", stats.points, points),
+
+            WinCriteria::Survive(secs) => format!("Survive {}/{}s", stats.time_taken.as_secs(), secs.ceil() as i32),
+
+            WinCriteria::DestroyTagged(tag) => {
+                let (destroyed, total) = stats.tagged
+                    .get(tag)
+                    .map_or((0, 0), |tagged| (tagged.destroyed, tagged.total));
+
+                format!("Destroy {} {}/{}", tag, destroyed, total)
+            }
+
+            WinCriteria::DeathZone(allowed) => format!("Lost {}/{}", stats.blocks_lost, allowed),
+
+            WinCriteria::All(criteria) => describe_all(criteria, stats, ", "),
+
+            WinCriteria::Any(criteria) => describe_all(criteria, stats, " or "),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            WinCriteria::BlockHitPercentage(pct) => {
+                if !(0.0..=1.0).contains(pct) {
+                    return Err(format!("BlockHitPercentage must be between 0.0 and 1.0, got {}", pct));
+                }
+            }
+
+            WinCriteria::Points(points) => {
+                if *points <= 0 {
+                    return Err(format!("Points must be positive, got {}", points));
+                }
+            }
+
+            WinCriteria::Survive(secs) => {
+                if !secs.is_finite() || *secs <= 0.0 {
+                    return Err(format!("Survive needs a positive number of seconds, got {}", secs));
+                }
+            }
+
+            WinCriteria::DestroyTagged(tag) => {
+                if tag.is_empty() {
+                    return Err("DestroyTagged needs a tag".to_string());
+                }
+            }
+
+            WinCriteria::DeathZone(allowed) => {
+                if *allowed < 0 {
+                    return Err(format!("DeathZone must not be negative, got {}", allowed));
+                }
+            }
+
+            WinCriteria::All(criteria) | WinCriteria::Any(criteria) => {
+                if criteria.is_empty() {
+                    return Err("All and Any need at least one criterion".to_string());
+                }
+
+                for criterion in criteria {
+                    criterion.validate()?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Tags the level's blocks have to carry
+    pub fn tags(&self) -> Vec<&str> {
+        match self {
+            WinCriteria::DestroyTagged(tag) => vec![tag.as_str()],
+            WinCriteria::All(criteria) | WinCriteria::Any(criteria) => criteria.iter().flat_map(|c| c.tags()).collect(),
+            _ => vec![],
+        }
+    }
+
+    fn is_guard(&self) -> bool {
+        matches!(self, WinCriteria::DeathZone(_))
+    }
+}
+
+fn describe_all(criteria: &[WinCriteria], stats: &MatchState, separator: &str) -> String {
+    criteria
+        .iter()
+        .map(|c| match c {
+            WinCriteria::All(_) | WinCriteria::Any(_) => format!("({})", c.describe(stats)),
+            _ => c.describe(stats),
+        })
+        .collect::<Vec<String>>()
+        .join(separator)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::utils::default;
+
+    use super::*;
+
+    #[test]
+    fn reaches_points_and_survives() {
+        let mut stats = MatchState {
+            blocks: 10,
+            points: 400,
+            ..default()
+        };
+
+        let crit = WinCriteria::Any(vec![WinCriteria::Points(1000), WinCriteria::Survive(30.0)]);
+
+        assert_eq!(crit.evaluate(&stats), CriteriaState::Open);
+        assert_eq!(crit.progress(&stats), 0.4);
+        assert_eq!(crit.describe(&stats), "Points 400/1000 or Survive 0/30s");
+
+        stats.time_taken = Duration::from_secs(30);
+        assert_eq!(crit.evaluate(&stats), CriteriaState::Met);
+
+        stats.time_taken = Duration::ZERO;
+        stats.blocks = 0;
+        assert_eq!(WinCriteria::Points(1000).evaluate(&stats), CriteriaState::Failed);
+        assert_eq!(crit.evaluate(&stats), CriteriaState::Open);
+    }
+
+    #[test]
+    fn destroys_tagged_blocks_while_guarding_the_death_zone() {
+        let mut stats = MatchState {
+            blocks: 10,
+            ..default()
+        };
+        stats.add_tagged_block("core");
+        stats.add_tagged_block("core");
+
+        let crit = WinCriteria::All(vec![
+            WinCriteria::DestroyTagged("core".to_string()),
+            WinCriteria::DeathZone(1),
+        ]);
+
+        stats.tagged_block_destroyed("core");
+        assert_eq!(crit.evaluate(&stats), CriteriaState::Open);
+        assert_eq!(crit.progress(&stats), 0.75);
+
+        stats.blocks_lost = 1;
+        stats.tagged_block_destroyed("core");
+        assert_eq!(crit.evaluate(&stats), CriteriaState::Met);
+
+        stats.blocks_lost = 2;
+        assert_eq!(crit.evaluate(&stats), CriteriaState::Failed);
+
+        // Alone the guard needs the level to be cleared
+        stats.blocks_lost = 0;
+        assert_eq!(WinCriteria::DeathZone(0).evaluate(&stats), CriteriaState::Open);
+        stats.blocks = 0;
+        assert_eq!(WinCriteria::DeathZone(0).evaluate(&stats), CriteriaState::Met);
+    }
+
+    #[test]
+    fn looses_with_a_lost_tagged_block() {
+        let mut stats = MatchState::default();
+        stats.add_tagged_block("core");
+        stats.tagged_block_lost("core");
+
+        assert_eq!(WinCriteria::DestroyTagged("core".to_string()).evaluate(&stats), CriteriaState::Failed);
+        assert_eq!(WinCriteria::DestroyTagged("other".to_string()).evaluate(&stats), CriteriaState::Open);
+    }
+
+    #[test]
+    fn validates_nested_criteria() {
+        let crit = WinCriteria::All(vec![
+            WinCriteria::Points(100),
+            WinCriteria::Any(vec![WinCriteria::DestroyTagged("a".to_string()), WinCriteria::Survive(-1.0)]),
+        ]);
+
+        assert!(crit.validate().is_err());
+        assert!(WinCriteria::Any(vec![]).validate().is_err());
+        assert!(WinCriteria::All(vec![WinCriteria::BlockHitPercentage(0.5)]).validate().is_ok());
+        assert_eq!(crit.tags(), vec!["a"]);
+    }
+}
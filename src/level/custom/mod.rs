@@ -0,0 +1,94 @@
+use bevy::app::App;
+use bevy::prelude::{Commands, Resource};
+use bevy::utils::HashMap;
+
+use crate::level::LevelDefinition;
+use crate::r#match::state::MatchState;
+
+
+/*
+    Builders for TargetLayout::Custom(name). A plugin registers its builders by name:
+
+        app.register_custom_layout("Conveyor", level_span_conveyor);
+
+    Levels that name a layout nobody registered are rejected when the campaign loads.
+ */
+pub trait LayoutBuilder: Send + Sync + 'static {
+    // Spawns the level's blocks and returns how many of them count towards winning
+    fn build(&self, commands: &mut Commands, level: &mut LevelDefinition, stats: &mut MatchState) -> i32;
+}
+
+impl<F> LayoutBuilder for F
+    where F: Fn(&mut Commands, &mut LevelDefinition, &mut MatchState) -> i32 + Send + Sync + 'static
+{
+    fn build(&self, commands: &mut Commands, level: &mut LevelDefinition, stats: &mut MatchState) -> i32 {
+        self(commands, level, stats)
+    }
+}
+
+
+#[derive(Resource, Default)]
+pub struct CustomLayouts {
+    builders: HashMap<String, Box<dyn LayoutBuilder>>,
+}
+
+impl CustomLayouts {
+    pub fn register(&mut self, name: &str, builder: impl LayoutBuilder) {
+        self.builders.insert(name.to_string(), Box::new(builder));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn LayoutBuilder> {
+        self.builders.get(name).map(|b| b.as_ref())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.builders.contains_key(name)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.builders.keys().map(|n| n.as_str()).collect();
+        names.sort();
+        names
+    }
+}
+
+
+pub trait RegisterCustomLayout {
+    fn register_custom_layout(&mut self, name: &str, builder: impl LayoutBuilder) -> &mut Self;
+}
+
+impl RegisterCustomLayout for App {
+    fn register_custom_layout(&mut self, name: &str, builder: impl LayoutBuilder) -> &mut Self {
+        self.init_resource::<CustomLayouts>();
+        self.world.resource_mut::<CustomLayouts>().register(name, builder);
+        self
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn three_blocks(_: &mut Commands, _: &mut LevelDefinition, _: &mut MatchState) -> i32 {
+        3
+    }
+
+    fn no_blocks(_: &mut Commands, _: &mut LevelDefinition, _: &mut MatchState) -> i32 {
+        0
+    }
+
+    #[test]
+    fn registers_builders_by_name() {
+        let mut app = App::new();
+        app
+            .register_custom_layout("Three", three_blocks)
+            .register_custom_layout("Conveyor", no_blocks);
+
+        let layouts = app.world.resource::<CustomLayouts>();
+
+        assert!(layouts.contains("Three"));
+        assert!(layouts.get("Nope").is_none());
+        assert_eq!(layouts.names(), vec!["Conveyor", "Three"]);
+    }
+}
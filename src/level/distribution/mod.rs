@@ -0,0 +1,109 @@
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+
+
+/*
+    Where the global pickups of a level drop. The match is cut into one stretch of hits per
+    pickup and each pickup drops at a random hit within its own stretch, in the order the
+    pickups are listed. As the stretches do not overlap every pickup gets a slot of its own
+    as long as there are at least as many blocks as pickups.
+
+    The same seed, block count and spacing always give the same slots.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum PickupSpacing {
+    // Stretches of equal length
+    #[default]
+    Uniform,
+    // Most pickups drop early in the match
+    FrontLoaded,
+    // Most pickups drop late in the match
+    BackLoaded,
+}
+
+impl PickupSpacing {
+    // Maps the progress through the pickups to the progress through the match
+    fn warp(&self, t: f32) -> f32 {
+        match self {
+            PickupSpacing::Uniform => t,
+            PickupSpacing::FrontLoaded => t * t,
+            PickupSpacing::BackLoaded => t * (2.0 - t),
+        }
+    }
+}
+
+
+// Returns the remaining block count at which each pickup drops. Pickups beyond the block count
+// get no slot.
+pub fn pickup_slots(pickups: usize, block_count: usize, spacing: PickupSpacing, seed: u64) -> Vec<usize> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let count = pickups.min(block_count);
+
+    // Stretch i covers the hits bounds[i]..bounds[i + 1], none of them may be empty
+    let mut bounds = vec![0; count + 1];
+    bounds[count] = block_count;
+
+    for i in 1..count {
+        let b = (block_count as f32 * spacing.warp(i as f32 / count as f32)) as usize;
+        bounds[i] = b.max(bounds[i - 1] + 1).min(block_count - (count - i));
+    }
+
+    (0..count)
+        .map(|i| {
+            let hit = rng.gen_range(bounds[i]..bounds[i + 1]);
+            block_count - 1 - hit
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPACINGS: [PickupSpacing; 3] = [PickupSpacing::Uniform, PickupSpacing::FrontLoaded, PickupSpacing::BackLoaded];
+
+    #[test]
+    fn every_pickup_gets_its_own_slot() {
+        for spacing in SPACINGS {
+            for block_count in 1..40 {
+                for pickups in 0..=block_count {
+                    let mut slots = pickup_slots(pickups, block_count, spacing, block_count as u64 * 31 + pickups as u64);
+
+                    assert_eq!(slots.len(), pickups);
+                    assert!(slots.iter().all(|s| *s < block_count));
+
+                    slots.sort();
+                    slots.dedup();
+                    assert_eq!(slots.len(), pickups, "{:?} {} pickups in {} blocks", spacing, pickups, block_count);
+                }
+            }
+
+            assert_eq!(pickup_slots(5, 3, spacing, 1).len(), 3);
+        }
+    }
+
+    #[test]
+    fn same_seed_same_slots() {
+        for spacing in SPACINGS {
+            assert_eq!(pickup_slots(4, 50, spacing, 7), pickup_slots(4, 50, spacing, 7));
+        }
+
+        assert_ne!(
+            (0..10).map(|seed| pickup_slots(4, 50, PickupSpacing::Uniform, seed)).collect::<Vec<_>>(),
+            (10..20).map(|seed| pickup_slots(4, 50, PickupSpacing::Uniform, seed)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn loads_the_front_or_the_back() {
+        // Early hits leave many blocks, so front loaded slots are high
+        let average = |spacing| (0..100)
+            .flat_map(|seed| pickup_slots(4, 100, spacing, seed))
+            .sum::<usize>() as f32 / 400.0;
+
+        assert!(average(PickupSpacing::FrontLoaded) > average(PickupSpacing::Uniform) + 10.0);
+        assert!(average(PickupSpacing::BackLoaded) < average(PickupSpacing::Uniform) - 10.0);
+    }
+}
@@ -0,0 +1,298 @@
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use crate::block::{Block, BlockBehaviour, BlockType};
+use crate::config::{BLOCK_GAP, EVADER_SPEED, VANISHER_PERIOD};
+use crate::level::{LevelDefinition, TargetLayout, WinCriteria};
+use crate::level::layout::{generate_block_grid, serialize_grid};
+use crate::pickups::PickupType;
+
+
+/*
+    Builds a level from a seed. The same seed and settings always produce the same level,
+    so a generated level can be shared as its seed and difficulty. Generated levels are
+    plain SparseGrid levels and can be saved and edited like any other level.
+ */
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratorSettings {
+    pub rows: usize,
+    // Odd, so symmetric levels have a center column
+    pub cols: usize,
+
+    // Chance that a slot of the pattern holds a block
+    pub density: f32,
+
+    // Chances for a block to be of the given type or behaviour, the rest are simple sitting ducks
+    pub hardlings: f32,
+    pub concrete: f32,
+    pub evaders: f32,
+    pub vanishers: f32,
+
+    // Obstacle blocks placed into the grid
+    pub obstacles: usize,
+
+    pub symmetric: bool,
+}
+
+impl GeneratorSettings {
+    // Difficulty from 0.0 (easy) to 1.0 (hard)
+    pub fn for_difficulty(difficulty: f32) -> Self {
+        let d = difficulty.clamp(0.0, 1.0);
+
+        GeneratorSettings {
+            rows: 3 + (d * 4.0).round() as usize,
+            cols: 7 + 2 * (d * 2.0).round() as usize,
+            density: 0.55 + 0.4 * d,
+            hardlings: 0.1 + 0.3 * d,
+            concrete: 0.25 * d,
+            evaders: 0.15 * d,
+            vanishers: 0.1 * d,
+            obstacles: (d * 4.0).round() as usize,
+            symmetric: true,
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Copy)]
+enum Pattern {
+    Full,
+    Checker,
+    Stripes,
+    Diamond,
+    Scatter,
+}
+
+impl Pattern {
+    fn pick(rng: &mut StdRng) -> Self {
+        match rng.gen_range(0..5) {
+            0 => Pattern::Full,
+            1 => Pattern::Checker,
+            2 => Pattern::Stripes,
+            3 => Pattern::Diamond,
+            _ => Pattern::Scatter,
+        }
+    }
+
+    fn contains(&self, row: usize, col: usize, rows: usize, cols: usize) -> bool {
+        match self {
+            Pattern::Full | Pattern::Scatter => true,
+            Pattern::Checker => (row + col) % 2 == 0,
+            Pattern::Stripes => row % 2 == 0,
+            Pattern::Diamond => {
+                let dx = (col as f32 - (cols - 1) as f32 / 2.0).abs() / cols as f32;
+                let dy = (row as f32 - (rows - 1) as f32 / 2.0).abs() / rows as f32;
+
+                dx + dy <= 0.6
+            }
+        }
+    }
+}
+
+
+pub fn generate_level(seed: u64, settings: &GeneratorSettings) -> LevelDefinition {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let rows = settings.rows.max(1);
+    let cols = settings.cols.max(1) | 1;
+    let gap = BLOCK_GAP;
+    let positions = generate_block_grid(rows, cols, gap);
+
+    let pattern = Pattern::pick(&mut rng);
+    let density = match pattern {
+        // Scatter relies on density alone, the others keep most of their shape
+        Pattern::Scatter => settings.density,
+        _ => (settings.density + 0.3).min(1.0),
+    };
+
+    let mut cells: Vec<Option<(BlockType, BlockBehaviour)>> = vec![None; rows * cols];
+
+    // Only the left half and the center are rolled for symmetric levels, the rest is mirrored
+    let rolled_cols = if settings.symmetric { cols / 2 + 1 } else { cols };
+
+    for row in 0..rows {
+        for col in 0..rolled_cols {
+            if !pattern.contains(row, col, rows, cols) || rng.gen::<f32>() >= density {
+                continue;
+            }
+
+            cells[row * cols + col] = Some((roll_type(&mut rng, settings), roll_behaviour(&mut rng, settings)));
+        }
+    }
+
+    let hittable = |cell: &Option<(BlockType, BlockBehaviour)>| match cell {
+        Some((block_type, _)) => *block_type != BlockType::Obstacle,
+        None => false,
+    };
+
+    // Obstacles go into empty slots, never into the front row (the last one, nearest to the
+    // paddle) so the grid stays reachable
+    for _ in 0..settings.obstacles {
+        if rows < 2 {
+            break;
+        }
+
+        let row = rng.gen_range(0..rows - 1);
+        let col = rng.gen_range(0..rolled_cols);
+        let cell = &mut cells[row * cols + col];
+
+        if cell.is_none() {
+            *cell = Some((BlockType::Obstacle, BlockBehaviour::SittingDuck));
+        }
+    }
+
+    if settings.symmetric {
+        for row in 0..rows {
+            for col in 0..cols / 2 {
+                cells[row * cols + cols - 1 - col] = cells[row * cols + col]
+                    .as_ref()
+                    .map(|(block_type, behaviour)| (block_type.clone(), mirrored(behaviour)));
+            }
+        }
+    }
+
+    if !cells.iter().any(hittable) {
+        cells[cols / 2] = Some((BlockType::Simple, BlockBehaviour::SittingDuck));
+    }
+
+    let blocks: Vec<Block> = cells
+        .into_iter()
+        .zip(positions)
+        .filter_map(|(cell, position)| cell.map(|(block_type, behaviour)| Block {
+            position,
+            block_type,
+            behaviour,
+            ..Default::default()
+        }))
+        .collect();
+
+    let hittable_count = blocks.iter().filter(|b| b.block_type != BlockType::Obstacle).count();
+
+    let mut global_pickups = vec![];
+    for _ in 0..rng.gen_range(0..=hittable_count.min(3)) {
+        global_pickups.push(match rng.gen_range(0..3) {
+            0 => PickupType::Grabber(1),
+            _ => PickupType::MoreBalls(1),
+        });
+    }
+
+    let layout = serialize_grid(&blocks, gap).expect("generated blocks are on the grid");
+
+    LevelDefinition {
+        simultaneous_balls: if settings.rows > 5 { 2 } else { 1 },
+        win_criteria: WinCriteria::BlockHitPercentage(1.0),
+        targets: TargetLayout::SparseGrid(layout, gap),
+        global_pickups,
+        ..Default::default()
+    }
+}
+
+
+fn roll_type(rng: &mut StdRng, settings: &GeneratorSettings) -> BlockType {
+    let r: f32 = rng.gen();
+
+    if r < settings.concrete {
+        BlockType::Concrete
+    } else if r < settings.concrete + settings.hardlings {
+        BlockType::Hardling
+    } else {
+        BlockType::Simple
+    }
+}
+
+fn roll_behaviour(rng: &mut StdRng, settings: &GeneratorSettings) -> BlockBehaviour {
+    let r: f32 = rng.gen();
+
+    if r < settings.evaders {
+        match rng.gen_range(0..4) {
+            0 => BlockBehaviour::EvaderR(EVADER_SPEED),
+            1 => BlockBehaviour::EvaderL(EVADER_SPEED),
+            2 => BlockBehaviour::EvaderU(EVADER_SPEED),
+            _ => BlockBehaviour::EvaderD(EVADER_SPEED),
+        }
+    } else if r < settings.evaders + settings.vanishers {
+        BlockBehaviour::Vanisher(VANISHER_PERIOD, 0.0)
+    } else {
+        BlockBehaviour::SittingDuck
+    }
+}
+
+fn mirrored(behaviour: &BlockBehaviour) -> BlockBehaviour {
+    match behaviour {
+        BlockBehaviour::EvaderR(speed) => BlockBehaviour::EvaderL(*speed),
+        BlockBehaviour::EvaderL(speed) => BlockBehaviour::EvaderR(*speed),
+        b => b.clone(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::Vec2;
+
+    use super::*;
+    use crate::level::campaign::parse_level;
+    use crate::level::layout::{generate_block_grid, interpret_grid};
+
+    fn layout_of(level: &LevelDefinition) -> &String {
+        match &level.targets {
+            TargetLayout::SparseGrid(layout, _) => layout,
+            _ => panic!("not a sparse grid"),
+        }
+    }
+
+    #[test]
+    fn same_seed_same_level() {
+        let settings = GeneratorSettings::for_difficulty(0.6);
+
+        for seed in 0..50 {
+            let a = generate_level(seed, &settings);
+            let b = generate_level(seed, &settings);
+
+            assert_eq!(layout_of(&a), layout_of(&b));
+            assert_eq!(a.global_pickups.len(), b.global_pickups.len());
+        }
+
+        assert_ne!(layout_of(&generate_level(1, &settings)), layout_of(&generate_level(2, &settings)));
+    }
+
+    #[test]
+    fn generated_levels_are_valid_and_symmetric() {
+        for seed in 0..200 {
+            let difficulty = (seed % 5) as f32 / 4.0;
+            let level = generate_level(seed, &GeneratorSettings::for_difficulty(difficulty));
+            let blocks = interpret_grid(layout_of(&level), BLOCK_GAP, true).unwrap();
+
+            assert!(blocks.iter().any(|b| b.block_type != BlockType::Obstacle), "seed {}", seed);
+
+            for block in &blocks {
+                let mirror = blocks.iter().find(|b| (b.position - Vec2::new(-block.position.x, block.position.y)).length() < 0.01);
+                assert!(mirror.is_some(), "seed {} has no mirror for {:?}", seed, block.position);
+                assert_eq!(mirror.unwrap().block_type, block.block_type);
+            }
+
+            let source = ron::to_string(&level).unwrap();
+            assert!(parse_level(&source).is_ok(), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn keeps_front_row_free_of_obstacles() {
+        let settings = GeneratorSettings::for_difficulty(1.0);
+        let front = generate_block_grid(settings.rows, settings.cols, BLOCK_GAP).last().unwrap().y;
+        let mut obstacles = 0;
+
+        for seed in 0..200 {
+            let level = generate_level(seed, &settings);
+            let blocks = interpret_grid(layout_of(&level), BLOCK_GAP, true).unwrap();
+
+            for block in blocks.iter().filter(|b| b.block_type == BlockType::Obstacle) {
+                assert!((block.position.y - front).abs() > 0.01, "seed {} has an obstacle at {:?}", seed, block.position);
+                obstacles += 1;
+            }
+        }
+
+        assert!(obstacles > 0);
+    }
+}
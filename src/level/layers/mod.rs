@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::{LAYER_HEIGHT, MAX_LAYER};
+use crate::level::TargetLayout;
+
+
+/*
+    Blocks can be stacked in up to three layers, -1 below, 0 at the height of the arena floor
+    and 1 above it. The level's targets fill layer 0, further layers are listed with their own
+    layout:
+
+        targets: SparseGrid("AA AA AA", 2.0),
+        layers: [
+            (layer: 1, targets: SparseGrid("BA .. BA", 2.0)),
+        ],
+
+    Named trigger groups are shared by all layers, [door] is the same group in every layout
+    of the level.
+
+    The ball travels on one layer at a time. It takes the layer closest to the ship's elevation
+    when it is launched or bounces off the ship, Lift obstacles and portals move it between
+    layers on the way.
+ */
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlockLayer {
+    pub layer: i32,
+    pub targets: TargetLayout,
+}
+
+
+// Height of the blocks and balls of a layer
+pub fn layer_elevation(layer: i32) -> f32 {
+    layer.clamp(-MAX_LAYER, MAX_LAYER) as f32 * LAYER_HEIGHT
+}
+
+// Layer closest to a height, used to snap balls to the ship's elevation
+pub fn nearest_layer(y: f32) -> i32 {
+    ((y / LAYER_HEIGHT).round() as i32).clamp(-MAX_LAYER, MAX_LAYER)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snaps_to_the_nearest_layer() {
+        assert_eq!(nearest_layer(0.0), 0);
+        assert_eq!(nearest_layer(LAYER_HEIGHT * 0.6), 1);
+        assert_eq!(nearest_layer(-LAYER_HEIGHT * 0.4), 0);
+        assert_eq!(nearest_layer(-LAYER_HEIGHT * 3.0), -MAX_LAYER);
+
+        for layer in -MAX_LAYER..=MAX_LAYER {
+            assert_eq!(nearest_layer(layer_elevation(layer)), layer);
+        }
+    }
+}
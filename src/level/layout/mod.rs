@@ -1,9 +1,105 @@
+use std::f32::consts::{FRAC_PI_2, TAU};
+use std::fmt;
+
+use bevy::utils::HashMap;
+
 use bevy::math::Vec2;
 use bevy::utils::default;
+use serde::{Deserialize, Serialize};
 use crate::block::{Block, BlockBehaviour, BlockType};
 use crate::block::trigger::{TriggerGroup, TriggerType};
 
-use crate::config::{BLOCK_DEPTH, BLOCK_WIDTH, BLOCK_WIDTH_H};
+use crate::pickups::PickupType;
+use crate::config::{BLOCK_DEPTH, BLOCK_WIDTH, BLOCK_WIDTH_H, EVADER_SPEED, EXPLOSIVE_RADIUS, FIELD_FALLOFF, FIELD_RADIUS, FIELD_STRENGTH, REGENERATION_DELAY, REPULSOR_FORCE, REPULSOR_THRESHOLD, SPINNER_SPEED, VANISHER_PERIOD};
+
+
+/*
+    Each 2 to 4 character tuple describes one block
+
+    1st Character
+        How many hits can a block take:
+        A = 1,
+        B = 2,
+        C = 3,
+        D = 1 only top
+        Z = unbreakable
+
+        Z is used for obstacles and do not count as blocks when determining of the
+        player has finished the level
+
+    2nd Character
+        What behaviour does the block have
+        A - Nothing
+        B - Spinner - Turns continuously, the ball picks up speed from its faces
+        C - Vanisher - questionable as well
+        D - Repulsor
+        E - Evader first movement to the right
+        F - Evader first movement to the left
+        G - Evader first movement up
+        H - Evader first movement down
+        I - Portal - Balls pass through it. Two portals in the same trigger group are a pair, a ball
+            entering one comes out of the other. A portal alone in its group is a trigger target,
+            it teleports the ball from the trigger to itself. Portals rest for a moment after use.
+        J - Explosive - Takes a hit point from every block around it when destroyed, explosives
+            set off this way go off a moment later
+        K - Splitter - Breaks into two half size blocks moving apart, counted as three blocks
+        L - Regenerating - Heals one hit point after some time without a hit, use it with B and C
+        M - Field - Pulls the ball in, or pushes it away like a repulsor with a positive strength.
+            Overlapping fields add up.
+
+    3rd Character (optional)
+        Triggertype:
+        A - Start Trigger
+        B - Stop Trigger
+        C - StartStop Trigger
+        R - Receiver that starts stopped
+        S - Receiver that starts started
+
+        4th Character (mandatory if char 3 exists)
+        Triggergroup 0..=9
+
+        Instead of the 4th character the group can be given in brackets, either as a
+        number or as a name: ZIR[12], ZIR[portalA]. Names are letters, digits and '_'.
+        Each name gets an id that no numbered group of the layout uses. A name that is
+        used only once is reported as a LayoutWarning, it can not connect anything.
+
+    Parameters (optional)
+        A slot can end in parameters for its behaviour: AE{speed=80}, AC{period=0.5,phase=0.25},
+        ZCR[door]{period=2}. Parameters that are not given keep their default.
+
+        Spinner (B)
+            speed - degrees per second, counter clockwise seen from above, negative values turn
+                    clockwise, default 90
+        Evaders (E, F, G, H)
+            speed - units per second, default 50
+        Vanisher (C)
+            period - seconds between appearing and vanishing, default 1
+            phase - offset of the first toggle as a fraction of the period, 0 <= phase < 1
+        Repulsor (D)
+            threshold - distance at which the ball is pushed away, default 20
+            force - maximum force, default 850
+        Field (M)
+            radius - distance from the block's center that the field reaches, default 30
+            strength - force at the block, negative values attract, default -600
+            falloff - 0 keeps the strength up to the radius, 1 fades linearly, 2 quadratically,
+                      default 1
+        Explosive (J)
+            radius - distance from the block's center that the explosion reaches, default 20
+        Regenerating (L)
+            delay - seconds without a hit until a hit point is healed, default 3
+        Portal (I)
+            exit - degrees the ball's velocity is turned by when it comes out of this portal,
+                   counter clockwise seen from above, default 0
+
+        Any block that can be destroyed can carry a tag, which win criteria refer to:
+        AA{tag=core}, AE{speed=80,tag=core}. Tags are letters, digits and '_'.
+
+        It can also drop a pickup when it is destroyed, given like in the level file:
+        AA{drop=Grabber(2)}, BE{drop=MoreBalls(1),tag=core}. The amount has to be positive.
+
+    Unknown characters are not guessed. interpret_grid reports them as a LayoutError
+    with the line and column of the offending slot.
+ */
 
 
 pub fn generate_block_grid(
@@ -41,7 +137,92 @@ pub fn generate_block_grid(
 }
 
 
-pub fn make_block(b_type: char, b_beh: char, b_trigger: Option<char>, b_trigger_group: Option<char>, pos: Vec2) -> Option<Block> {
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayoutErrorKind {
+    EmptyLayout,
+    SlotTooShort,
+    SlotTooLong,
+    UnknownBlockType(char),
+    UnknownBehaviour(char),
+    UnknownTriggerType(char),
+    MissingTriggerGroup,
+    InvalidTriggerGroup(char),
+    InvalidTriggerGroupName(String),
+    UnclosedTriggerGroup,
+    GroupWithoutTrigger,
+    UnclosedParameters,
+    // The name=value pair that could not be read or is out of range
+    InvalidParameter(String),
+    // Parameter name the block's behaviour does not have
+    UnknownParameter(String),
+    // Expected column count, found column count
+    RaggedRow(usize, usize),
+}
+
+// Line and column are 1-based and refer to the layout string
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutError {
+    pub line: usize,
+    pub column: usize,
+    pub token: String,
+    pub kind: LayoutErrorKind,
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+
+        match &self.kind {
+            LayoutErrorKind::EmptyLayout => write!(f, "layout has no rows"),
+            LayoutErrorKind::SlotTooShort => write!(f, "`{}` is too short, a slot needs at least 2 characters", self.token),
+            LayoutErrorKind::SlotTooLong => write!(f, "`{}` is too long, a slot has at most 4 characters", self.token),
+            LayoutErrorKind::UnknownBlockType(c) => write!(f, "unknown block type '{}' in `{}`", c, self.token),
+            LayoutErrorKind::UnknownBehaviour(c) => write!(f, "unknown behaviour '{}' in `{}`", c, self.token),
+            LayoutErrorKind::UnknownTriggerType(c) => write!(f, "unknown trigger type '{}' in `{}`", c, self.token),
+            LayoutErrorKind::MissingTriggerGroup => write!(f, "`{}` has a trigger type but no trigger group", self.token),
+            LayoutErrorKind::InvalidTriggerGroup(c) => write!(f, "invalid trigger group '{}' in `{}`", c, self.token),
+            LayoutErrorKind::InvalidTriggerGroupName(name) => write!(f, "invalid trigger group name `{}` in `{}`", name, self.token),
+            LayoutErrorKind::UnclosedTriggerGroup => write!(f, "`{}` has a trigger group without a closing ']'", self.token),
+            LayoutErrorKind::GroupWithoutTrigger => write!(f, "`{}` has a trigger group but no trigger type", self.token),
+            LayoutErrorKind::UnclosedParameters => write!(f, "`{}` has parameters without a closing '}}'", self.token),
+            LayoutErrorKind::InvalidParameter(p) => write!(f, "invalid parameter `{}` in `{}`", p, self.token),
+            LayoutErrorKind::UnknownParameter(name) => write!(f, "`{}` has no parameter `{}`", self.token, name),
+            LayoutErrorKind::RaggedRow(expected, found) => write!(f, "row has {} columns, expected {}", found, expected),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutWarning {
+    pub line: usize,
+    pub column: usize,
+    pub token: String,
+    pub group: String,
+}
+
+impl fmt::Display for LayoutWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: trigger group `{}` in `{}` is used only once", self.line, self.column, self.group, self.token)
+    }
+}
+
+
+pub struct Layout {
+    pub blocks: Vec<Block>,
+    pub warnings: Vec<LayoutWarning>,
+    // Index into blocks and trigger group name of every block with a named group
+    pub names: Vec<(usize, String)>,
+}
+
+
+pub fn make_block(b_type: char, b_beh: char, b_trigger: Option<char>, b_trigger_group: Option<TriggerGroup>, pos: Vec2) -> Result<Option<Block>, LayoutErrorKind> {
+    if b_type == '.' && b_beh == '.' && b_trigger.is_none() {
+        return Ok(None);
+    }
+
     let t = match b_type  {
         'A' => BlockType::Simple,
         'B' => BlockType::Hardling,
@@ -49,25 +230,25 @@ pub fn make_block(b_type: char, b_beh: char, b_trigger: Option<char>, b_trigger_
         'D' => BlockType::SimpleTop,
         'Z' => BlockType::Obstacle,
 
-        '.' => return None,
-
-        _ => BlockType::Simple
+        _ => return Err(LayoutErrorKind::UnknownBlockType(b_type))
     };
 
     let b = match b_beh {
         'A' => BlockBehaviour::SittingDuck,
-        'B' => BlockBehaviour::Spinner,
-        'C' => BlockBehaviour::Vanisher,
-        'D' => BlockBehaviour::Repuslor,
-        'E' => BlockBehaviour::EvaderR(50.0),
-        'F' => BlockBehaviour::EvaderL(50.0),
-        'G' => BlockBehaviour::EvaderU(50.0),
-        'H' => BlockBehaviour::EvaderD(50.0),
-        'I' => BlockBehaviour::Portal,
-
-        '.' => return None,
-
-        _ => BlockBehaviour::SittingDuck
+        'B' => BlockBehaviour::Spinner(SPINNER_SPEED),
+        'C' => BlockBehaviour::Vanisher(VANISHER_PERIOD, 0.0),
+        'D' => BlockBehaviour::Repuslor(REPULSOR_THRESHOLD, REPULSOR_FORCE),
+        'E' => BlockBehaviour::EvaderR(EVADER_SPEED),
+        'F' => BlockBehaviour::EvaderL(EVADER_SPEED),
+        'G' => BlockBehaviour::EvaderU(EVADER_SPEED),
+        'H' => BlockBehaviour::EvaderD(EVADER_SPEED),
+        'I' => BlockBehaviour::Portal(0.0),
+        'J' => BlockBehaviour::Explosive(EXPLOSIVE_RADIUS),
+        'K' => BlockBehaviour::Splitter,
+        'L' => BlockBehaviour::Regenerating(REGENERATION_DELAY),
+        'M' => BlockBehaviour::Field(FIELD_RADIUS, FIELD_STRENGTH, FIELD_FALLOFF),
+
+        _ => return Err(LayoutErrorKind::UnknownBehaviour(b_beh))
     };
 
     let tt = if let Some(t) = b_trigger {
@@ -78,46 +259,260 @@ pub fn make_block(b_type: char, b_beh: char, b_trigger: Option<char>, b_trigger_
           'R' => Some(TriggerType::ReceiverStartingInactive),
           'S' => Some(TriggerType::ReceiverStartingActive),
 
-          _ => None
+          _ => return Err(LayoutErrorKind::UnknownTriggerType(t))
       }
     } else {
         None
     };
 
-    let tg = if let Some(g) = b_trigger_group {
-      match g {
-
-          '0'..='9' => Some((g as TriggerGroup) - 48),
-
-          _ => None
-      }
-    } else {
-        None
-    };
+    if tt.is_some() && b_trigger_group.is_none() {
+        return Err(LayoutErrorKind::MissingTriggerGroup);
+    }
 
-    Some(Block {
+    Ok(Some(Block {
         behaviour: b,
         block_type: t,
         position: pos,
         trigger_type: tt,
-        trigger_group: tg,
+        trigger_group: b_trigger_group,
         ..default()
-    })
+    }))
 }
 
 
-pub fn interpret_grid(layout: &String, gap: f32) -> Option<Vec<Block>> {
+// Returns the block and the name of its trigger group, which is resolved once the whole
+// layout is known
+fn parse_slot(slot: &str, pos: Vec2) -> Result<Option<(Block, Option<&str>)>, LayoutErrorKind> {
+    let (slot, params) = match slot.find('{') {
+        None => (slot, None),
+        Some(i) => match slot[i + 1..].strip_suffix('}') {
+            Some(params) => (&slot[..i], Some(params)),
+            None => return Err(LayoutErrorKind::UnclosedParameters),
+        }
+    };
+
+    let (body, bracket) = match slot.find('[') {
+        None => (slot, None),
+        Some(i) => match slot[i + 1..].strip_suffix(']') {
+            Some(group) => (&slot[..i], Some(group)),
+            None => return Err(LayoutErrorKind::UnclosedTriggerGroup),
+        }
+    };
+
+    let chars: Vec<char> = body.chars().collect();
+    let max_len = if bracket.is_some() { 3 } else { 4 };
+
+    if chars.len() < 2 {
+        return Err(LayoutErrorKind::SlotTooShort);
+    }
+
+    if chars.len() > max_len {
+        return Err(LayoutErrorKind::SlotTooLong);
+    }
+
+    let (group, name) = match (chars.get(2), chars.get(3), bracket) {
+        (None, _, Some(_)) => return Err(LayoutErrorKind::GroupWithoutTrigger),
+
+        (_, Some(g), _) => match g.to_digit(10) {
+            Some(d) => (Some(d as TriggerGroup), None),
+            None => return Err(LayoutErrorKind::InvalidTriggerGroup(*g)),
+        },
+
+        (_, None, Some(b)) if !b.is_empty() && b.chars().all(|c| c.is_ascii_digit()) => match b.parse::<TriggerGroup>() {
+            Ok(id) => (Some(id), None),
+            Err(_) => return Err(LayoutErrorKind::InvalidTriggerGroupName(b.to_string())),
+        },
+
+        (_, None, Some(b)) => {
+            if b.is_empty() || !b.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(LayoutErrorKind::InvalidTriggerGroupName(b.to_string()));
+            }
+
+            // Placeholder until the name is resolved
+            (Some(0), Some(b))
+        }
+
+        (_, None, None) => (None, None),
+    };
+
+    match (make_block(chars[0], chars[1], chars.get(2).copied(), group, pos)?, params) {
+        (Some(mut block), Some(params)) => {
+            let (tag, drop, params) = split_block_parameters(params)?;
+
+            // Obstacles are never destroyed
+            if block.block_type == BlockType::Obstacle {
+                if tag.is_some() {
+                    return Err(LayoutErrorKind::UnknownParameter("tag".to_string()));
+                }
+
+                if drop.is_some() {
+                    return Err(LayoutErrorKind::UnknownParameter("drop".to_string()));
+                }
+            }
+
+            block.behaviour = apply_parameters(block.behaviour, &params)?;
+            block.tag = tag;
+            block.drop = drop;
+            Ok(Some((block, name)))
+        }
+
+        (None, Some(params)) => apply_parameters(BlockBehaviour::SittingDuck, params).map(|_| None),
+
+        (block, None) => Ok(block.map(|block| (block, name))),
+    }
+}
+
+
+fn parameter_names(behaviour: &BlockBehaviour) -> &'static [&'static str] {
+    match behaviour {
+        BlockBehaviour::Spinner(_) |
+        BlockBehaviour::EvaderR(_) | BlockBehaviour::EvaderL(_) |
+        BlockBehaviour::EvaderU(_) | BlockBehaviour::EvaderD(_) => &["speed"],
+        BlockBehaviour::Vanisher(_, _) => &["period", "phase"],
+        BlockBehaviour::Repuslor(_, _) => &["threshold", "force"],
+        BlockBehaviour::Explosive(_) => &["radius"],
+        BlockBehaviour::Regenerating(_) => &["delay"],
+        BlockBehaviour::Portal(_) => &["exit"],
+        BlockBehaviour::Field(_, _, _) => &["radius", "strength", "falloff"],
+        _ => &[],
+    }
+}
+
+// Tag and drop are the parameters that are not numbers, they are taken out before the others are applied
+fn split_block_parameters(params: &str) -> Result<(Option<String>, Option<PickupType>, String), LayoutErrorKind> {
+    let mut tag = None;
+    let mut drop = None;
+    let mut rest = vec![];
+
+    for param in params.split(',') {
+        let invalid = || LayoutErrorKind::InvalidParameter(param.to_string());
+
+        match param.split_once('=') {
+            Some(("tag", name)) => {
+                if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                    return Err(invalid());
+                }
+
+                tag = Some(name.to_string());
+            }
+
+            Some(("drop", pickup)) => {
+                let (kind, amount) = pickup
+                    .strip_suffix(')')
+                    .and_then(|p| p.split_once('('))
+                    .ok_or_else(invalid)?;
+
+                drop = Some(match (kind, amount.parse::<i16>()) {
+                    ("MoreBalls", Ok(n)) if n > 0 => PickupType::MoreBalls(n as i32),
+                    ("Grabber", Ok(n)) if n > 0 => PickupType::Grabber(n),
+                    _ => return Err(invalid()),
+                });
+            }
+
+            _ => rest.push(param),
+        }
+    }
+
+    Ok((tag, drop, rest.join(",")))
+}
+
+// Applies the name=value pairs between the braces of a slot
+fn apply_parameters(behaviour: BlockBehaviour, params: &str) -> Result<BlockBehaviour, LayoutErrorKind> {
+    let mut behaviour = behaviour;
 
+    if params.is_empty() {
+        return Ok(behaviour);
+    }
+
+    for param in params.split(',') {
+        let invalid = || LayoutErrorKind::InvalidParameter(param.to_string());
+
+        let (name, value) = param.split_once('=').ok_or_else(invalid)?;
+        let value: f32 = value.parse().map_err(|_| invalid())?;
+
+        if !parameter_names(&behaviour).contains(&name) {
+            return Err(LayoutErrorKind::UnknownParameter(name.to_string()));
+        }
+
+        behaviour = match (name, behaviour) {
+            ("speed", BlockBehaviour::Spinner(_)) if value.is_finite() => BlockBehaviour::Spinner(value),
+            ("speed", BlockBehaviour::EvaderR(_)) if value > 0.0 => BlockBehaviour::EvaderR(value),
+            ("speed", BlockBehaviour::EvaderL(_)) if value > 0.0 => BlockBehaviour::EvaderL(value),
+            ("speed", BlockBehaviour::EvaderU(_)) if value > 0.0 => BlockBehaviour::EvaderU(value),
+            ("speed", BlockBehaviour::EvaderD(_)) if value > 0.0 => BlockBehaviour::EvaderD(value),
+            ("period", BlockBehaviour::Vanisher(_, phase)) if value > 0.0 => BlockBehaviour::Vanisher(value, phase),
+            ("phase", BlockBehaviour::Vanisher(period, _)) if (0.0..1.0).contains(&value) => BlockBehaviour::Vanisher(period, value),
+            ("threshold", BlockBehaviour::Repuslor(_, force)) if value > 0.0 => BlockBehaviour::Repuslor(value, force),
+            ("force", BlockBehaviour::Repuslor(threshold, _)) if value >= 0.0 => BlockBehaviour::Repuslor(threshold, value),
+            ("radius", BlockBehaviour::Explosive(_)) if value > 0.0 => BlockBehaviour::Explosive(value),
+            ("delay", BlockBehaviour::Regenerating(_)) if value > 0.0 => BlockBehaviour::Regenerating(value),
+            ("exit", BlockBehaviour::Portal(_)) if value.is_finite() => BlockBehaviour::Portal(value),
+            ("radius", BlockBehaviour::Field(_, strength, falloff)) if value > 0.0 => BlockBehaviour::Field(value, strength, falloff),
+            ("strength", BlockBehaviour::Field(radius, _, falloff)) if value.is_finite() => BlockBehaviour::Field(radius, value, falloff),
+            ("falloff", BlockBehaviour::Field(radius, strength, _)) if value >= 0.0 => BlockBehaviour::Field(radius, strength, value),
+
+            // Known name, but the value is out of range
+            _ => return Err(invalid()),
+        };
+    }
+
+    Ok(behaviour)
+}
+
+
+// Splits a line into its slots, each with the column it starts at
+fn split_slots(line: &str) -> Vec<(usize, &str)> {
     let mut res = vec![];
+    let mut column = 1;
+
+    for slot in line.split(' ') {
+        if !slot.is_empty() {
+            res.push((column, slot));
+        }
+        column += slot.chars().count() + 1;
+    }
+
+    res
+}
 
-    let mut lines:Vec<&str> = layout.split("\n").collect();
-    let mut line_count = lines.len();
 
-    let first = lines.get(0).clone().unwrap().split(" ");;
+// In strict mode every row must have as many slots as the first one. Otherwise rows may
+// differ and the grid is sized from the widest row.
+pub fn interpret_grid(layout: &String, gap: f32, strict: bool) -> Result<Vec<Block>, LayoutError> {
+    interpret_layout(layout, gap, strict).map(|layout| layout.blocks)
+}
+
+pub fn interpret_layout(layout: &String, gap: f32, strict: bool) -> Result<Layout, LayoutError> {
 
-    let cols = first.collect::<Vec<&str>>().len();
+    let mut res = vec![];
+    // Index into res, group name, line, column and slot of every named group
+    let mut named = vec![];
 
+    let lines: Vec<(usize, &str, Vec<(usize, &str)>)> = layout
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line, split_slots(line)))
+        .collect();
 
+    // Blank lines before the first and after the last row are not rows
+    let first = lines.iter().position(|(_, _, slots)| !slots.is_empty());
+    let last = lines.iter().rposition(|(_, _, slots)| !slots.is_empty());
+
+    let rows = match (first, last) {
+        (Some(first), Some(last)) => &lines[first..=last],
+        _ => return Err(LayoutError {
+            line: 1,
+            column: 1,
+            token: String::new(),
+            kind: LayoutErrorKind::EmptyLayout,
+        })
+    };
+
+    let cols = if strict {
+        rows[0].2.len()
+    } else {
+        rows.iter().map(|(_, _, slots)| slots.len()).max().unwrap()
+    };
 
 
     let x_step = BLOCK_WIDTH + gap;
@@ -126,8 +521,20 @@ pub fn interpret_grid(layout: &String, gap: f32) -> Option<Vec<Block>> {
     let mut y = -30.0 - 4.0 * (BLOCK_DEPTH + gap);
     let y_step = BLOCK_DEPTH + gap;
 
-    for line in lines {
-        line_count += 1;
+    for (line, text, slots) in rows {
+        if strict && slots.len() != cols {
+            let (column, token) = match slots.get(cols) {
+                Some((column, slot)) => (*column, slot.to_string()),
+                None => (text.chars().count() + 1, String::new())
+            };
+
+            return Err(LayoutError {
+                line: *line,
+                column,
+                token,
+                kind: LayoutErrorKind::RaggedRow(cols, slots.len()),
+            });
+        }
 
         let mut x = 0.0;
         if cols % 2 == 1 {
@@ -136,32 +543,486 @@ pub fn interpret_grid(layout: &String, gap: f32) -> Option<Vec<Block>> {
             x -= cols_h * x_step - gap / 2.0 - BLOCK_WIDTH_H;
         }
 
+        for (column, slot) in slots {
+            let pos_x = x;
+            x += x_step;
+
+            match parse_slot(slot, Vec2::new(pos_x, y)) {
+                Ok(None) => {}
+                Ok(Some((block, name))) => {
+                    if let Some(name) = name {
+                        named.push((res.len(), name, *line, *column, *slot));
+                    }
+                    res.push(block);
+                }
+                Err(kind) => return Err(LayoutError {
+                    line: *line,
+                    column: *column,
+                    token: slot.to_string(),
+                    kind,
+                })
+            }
+        }
+        y += y_step;
+    }
 
-        let slots = line.split(" ");
+    let warnings = resolve_group_names(&mut res, &named);
 
-        for slot in slots {
-            if slot.len() < 2 {
-                continue;
+    Ok(Layout {
+        blocks: res,
+        warnings,
+        names: named.iter().map(|(index, name, ..)| (*index, name.to_string())).collect(),
+    })
+}
+
+
+// Gives every named group an id that no numbered group uses. Names are given as the index of the
+// block, the name, and the line, column and slot it was found at.
+fn resolve_group_names(blocks: &mut Vec<Block>, named: &[(usize, &str, usize, usize, &str)]) -> Vec<LayoutWarning> {
+    let mut used: Vec<TriggerGroup> = blocks
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !named.iter().any(|(n, ..)| n == i))
+        .filter_map(|(_, block)| block.trigger_group)
+        .collect();
+
+    let mut ids: HashMap<&str, TriggerGroup> = HashMap::default();
+    let mut next: TriggerGroup = 0;
+
+    for (index, name, ..) in named.iter() {
+        let id = match ids.get(name) {
+            Some(id) => *id,
+            None => {
+                while used.contains(&next) {
+                    next += 1;
+                }
+                used.push(next);
+                ids.insert(*name, next);
+                next
             }
+        };
+
+        blocks[*index].trigger_group = Some(id);
+    }
+
+    named
+        .iter()
+        .filter(|(_, name, ..)| named.iter().filter(|(_, other, ..)| other == name).count() == 1)
+        .map(|(_, name, line, column, slot)| LayoutWarning {
+            line: *line,
+            column: *column,
+            token: slot.to_string(),
+            group: name.to_string(),
+        })
+        .collect()
+}
 
-            let pos_x = x;
-            x += x_step;
 
-            let b_type = slot.chars().nth(0).unwrap();
-            let b_beh = slot.chars().nth(1).unwrap();
-            let b_trigger_type = slot.chars().nth(2);
-            let b_trigger_group = slot.chars().nth(3);
 
+// Gives named groups the same id in all layouts that are spawned together, like the layers of a
+// level, and keeps them clear of the numbered groups of every layout
+pub fn share_group_names(layouts: &mut [Layout]) {
+    let mut used: Vec<TriggerGroup> = layouts
+        .iter()
+        .flat_map(|layout| layout.blocks
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !layout.names.iter().any(|(n, _)| n == i))
+            .filter_map(|(_, block)| block.trigger_group))
+        .collect();
+
+    let mut ids: HashMap<String, TriggerGroup> = HashMap::default();
+    let mut next: TriggerGroup = 0;
+
+    for layout in layouts.iter_mut() {
+        for (index, name) in &layout.names {
+            let id = *ids.entry(name.clone()).or_insert_with(|| {
+                while used.contains(&next) {
+                    next += 1;
+                }
+                used.push(next);
+                next
+            });
+
+            layout.blocks[*index].trigger_group = Some(id);
+        }
+    }
+}
+
+
+/*
+    Hex grids use the slot notation of SparseGrid, every second row is shifted right by half
+    a block so the rows interlock like bricks in a wall.
+ */
+pub fn interpret_hex_layout(layout: &String, gap: f32, strict: bool) -> Result<Layout, LayoutError> {
+    let mut layout = interpret_layout(layout, gap, strict)?;
+
+    let x_step = BLOCK_WIDTH + gap;
+    let y_step = BLOCK_DEPTH + gap;
+    let y0 = -30.0 - 4.0 * (BLOCK_DEPTH + gap);
+
+    for block in &mut layout.blocks {
+        let row = ((block.position.y - y0) / y_step).round() as i32;
+
+        if row % 2 == 1 {
+            block.position.x += x_step / 2.0;
+        }
+    }
+
+    Ok(layout)
+}
+
+
+/*
+    A ring of equal blocks around the center of a Radial layout. The blocks lie along the ring
+    with their top facing outwards. Rotation is given in degrees and turns the ring counter
+    clockwise seen from above, the first block of an unrotated ring sits right of the center.
+
+        targets: Radial((0.0, -50.0), [
+            (radius: 20.0, count: 6, slot: "BA"),
+            (radius: 40.0, count: 12, rotation: 15.0, slot: "AA{tag=core}"),
+        ]),
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Ring {
+    pub radius: f32,
+    pub count: usize,
+    pub rotation: f32,
+    // Block in slot notation
+    pub slot: String,
+}
+
+impl Default for Ring {
+    fn default() -> Self {
+        Ring {
+            radius: 30.0,
+            count: 8,
+            rotation: 0.0,
+            slot: "AA".to_string(),
+        }
+    }
+}
+
+/*
+    A single block of a Freeform layout, at an arena position (x, z) and turned by the rotation
+    in degrees, counter clockwise seen from above.
+
+        targets: Freeform([
+            (position: (-20.0, -60.0), rotation: 45.0, slot: "AA"),
+            (position: (20.0, -60.0), rotation: -45.0, slot: "AA"),
+        ]),
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Placement {
+    pub position: Vec2,
+    pub rotation: f32,
+    pub slot: String,
+}
+
+impl Default for Placement {
+    fn default() -> Self {
+        Placement {
+            position: Vec2::ZERO,
+            rotation: 0.0,
+            slot: "AA".to_string(),
+        }
+    }
+}
+
+// The line of errors and warnings is the number of the ring
+pub fn interpret_rings(center: Vec2, rings: &[Ring]) -> Result<Layout, LayoutError> {
+    let mut slots = vec![];
+
+    for ring in rings {
+        for i in 0..ring.count {
+            let angle = ring.rotation.to_radians() + TAU * i as f32 / ring.count as f32;
+            let position = center + ring.radius * Vec2::new(angle.cos(), -angle.sin());
+
+            slots.push((position, angle - FRAC_PI_2, ring.slot.as_str()));
+        }
+    }
+
+    let lines: Vec<usize> = rings
+        .iter()
+        .enumerate()
+        .flat_map(|(line, ring)| std::iter::repeat(line + 1).take(ring.count))
+        .collect();
+
+    interpret_placed_slots(&slots, &lines)
+}
+
+// The line of errors and warnings is the number of the placement
+pub fn interpret_freeform(placements: &[Placement]) -> Result<Layout, LayoutError> {
+    let slots: Vec<(Vec2, f32, &str)> = placements
+        .iter()
+        .map(|p| (p.position, p.rotation.to_radians(), p.slot.as_str()))
+        .collect();
+
+    let lines: Vec<usize> = (1..=placements.len()).collect();
+
+    interpret_placed_slots(&slots, &lines)
+}
+
+fn interpret_placed_slots(slots: &[(Vec2, f32, &str)], lines: &[usize]) -> Result<Layout, LayoutError> {
+    let mut res = vec![];
+    let mut named = vec![];
 
-            match make_block(b_type, b_beh, b_trigger_type, b_trigger_group, Vec2::new(pos_x, y)) {
-                None => {}
-                Some(block) => res.push(block)
+    for ((position, rotation, slot), line) in slots.iter().zip(lines) {
+        match parse_slot(slot, *position) {
+            Ok(None) => {}
+            Ok(Some((mut block, name))) => {
+                block.rotation = *rotation;
+
+                if let Some(name) = name {
+                    named.push((res.len(), name, *line, 1, *slot));
+                }
+                res.push(block);
             }
+            Err(kind) => return Err(LayoutError {
+                line: *line,
+                column: 1,
+                token: slot.to_string(),
+                kind,
+            })
         }
-        y += y_step;
     }
 
-    Some(res)
+    let warnings = resolve_group_names(&mut res, &named);
+
+    Ok(Layout {
+        blocks: res,
+        warnings,
+        names: named.iter().map(|(index, name, ..)| (*index, name.to_string())).collect(),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GridWriteError {
+    // Block position that does not sit on a grid cell
+    OffGrid(Vec2),
+    // Row and column that hold more than one block
+    Occupied(usize, usize),
+    // Block that the notation can not express
+    Unsupported(String),
+}
+
+impl fmt::Display for GridWriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GridWriteError::OffGrid(p) => write!(f, "block at ({}, {}) is not on the grid", p.x, p.y),
+            GridWriteError::Occupied(row, col) => write!(f, "more than one block in row {}, column {}", row + 1, col + 1),
+            GridWriteError::Unsupported(what) => write!(f, "can not be written as a slot: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for GridWriteError {}
+
+
+pub fn block_to_slot(block: &Block) -> Result<String, GridWriteError> {
+    let mut slot = String::new();
+
+    slot.push(match block.block_type {
+        BlockType::Simple => 'A',
+        BlockType::Hardling => 'B',
+        BlockType::Concrete => 'C',
+        BlockType::SimpleTop => 'D',
+        BlockType::Obstacle => 'Z',
+    });
+
+    slot.push(match block.behaviour {
+        BlockBehaviour::SittingDuck => 'A',
+        BlockBehaviour::Spinner(_) => 'B',
+        BlockBehaviour::Vanisher(_, _) => 'C',
+        BlockBehaviour::Repuslor(_, _) => 'D',
+        BlockBehaviour::EvaderR(_) => 'E',
+        BlockBehaviour::EvaderL(_) => 'F',
+        BlockBehaviour::EvaderU(_) => 'G',
+        BlockBehaviour::EvaderD(_) => 'H',
+        BlockBehaviour::Portal(_) => 'I',
+        BlockBehaviour::Explosive(_) => 'J',
+        BlockBehaviour::Splitter => 'K',
+        BlockBehaviour::Regenerating(_) => 'L',
+        BlockBehaviour::Field(_, _, _) => 'M',
+    });
+
+    match (&block.trigger_type, block.trigger_group) {
+        (None, None) => {}
+
+        // Group names are not kept, named groups are written as their id
+        (Some(t), Some(g)) => {
+            slot.push(match t {
+                TriggerType::Start => 'A',
+                TriggerType::Stop => 'B',
+                TriggerType::StartStop => 'C',
+                TriggerType::ReceiverStartingInactive => 'R',
+                TriggerType::ReceiverStartingActive => 'S',
+            });
+
+            if g <= 9 {
+                slot.push((b'0' + g as u8) as char);
+            } else {
+                slot.push_str(&format!("[{}]", g));
+            }
+        }
+
+        (t, g) => return Err(GridWriteError::Unsupported(format!("trigger {:?} in group {:?}", t, g)))
+    }
+
+    // Only parameters that differ from their default are written
+    let mut params = vec![];
+
+    match block.behaviour {
+        BlockBehaviour::Spinner(speed) => {
+            if speed != SPINNER_SPEED {
+                params.push(format!("speed={}", speed));
+            }
+        }
+
+        BlockBehaviour::EvaderR(speed) | BlockBehaviour::EvaderL(speed) |
+        BlockBehaviour::EvaderU(speed) | BlockBehaviour::EvaderD(speed) => {
+            if speed != EVADER_SPEED {
+                params.push(format!("speed={}", speed));
+            }
+        }
+
+        BlockBehaviour::Vanisher(period, phase) => {
+            if period != VANISHER_PERIOD {
+                params.push(format!("period={}", period));
+            }
+            if phase != 0.0 {
+                params.push(format!("phase={}", phase));
+            }
+        }
+
+        BlockBehaviour::Repuslor(threshold, force) => {
+            if threshold != REPULSOR_THRESHOLD {
+                params.push(format!("threshold={}", threshold));
+            }
+            if force != REPULSOR_FORCE {
+                params.push(format!("force={}", force));
+            }
+        }
+
+        BlockBehaviour::Explosive(radius) => {
+            if radius != EXPLOSIVE_RADIUS {
+                params.push(format!("radius={}", radius));
+            }
+        }
+
+        BlockBehaviour::Regenerating(delay) => {
+            if delay != REGENERATION_DELAY {
+                params.push(format!("delay={}", delay));
+            }
+        }
+
+        BlockBehaviour::Portal(exit) => {
+            if exit != 0.0 {
+                params.push(format!("exit={}", exit));
+            }
+        }
+
+        BlockBehaviour::Field(radius, strength, falloff) => {
+            if radius != FIELD_RADIUS {
+                params.push(format!("radius={}", radius));
+            }
+            if strength != FIELD_STRENGTH {
+                params.push(format!("strength={}", strength));
+            }
+            if falloff != FIELD_FALLOFF {
+                params.push(format!("falloff={}", falloff));
+            }
+        }
+
+        _ => {}
+    }
+
+    if let Some(tag) = &block.tag {
+        params.push(format!("tag={}", tag));
+    }
+
+    match block.drop {
+        Some(PickupType::MoreBalls(n)) => params.push(format!("drop=MoreBalls({})", n)),
+        Some(PickupType::Grabber(n)) => params.push(format!("drop=Grabber({})", n)),
+        None => {}
+    }
+
+    if !params.is_empty() {
+        slot.push('{');
+        slot.push_str(&params.join(","));
+        slot.push('}');
+    }
+
+    Ok(slot)
+}
+
+
+// Inverse of interpret_grid. The grid is centered on x = 0, so the column count is taken
+// from the block furthest from the center. Rows start at the first row interpret_grid uses.
+pub fn serialize_grid(blocks: &[Block], gap: f32) -> Result<String, GridWriteError> {
+    const EPSILON: f32 = 0.01;
+
+    let x_step = BLOCK_WIDTH + gap;
+    let y_step = BLOCK_DEPTH + gap;
+    let y0 = -30.0 - 4.0 * (BLOCK_DEPTH + gap);
+
+    // Twice the column offset from the center keeps grids with an even column count integral
+    let mut cells = vec![];
+    for block in blocks {
+        let d = 2.0 * block.position.x / x_step;
+        let r = (block.position.y - y0) / y_step;
+
+        if (d - d.round()).abs() > EPSILON || (r - r.round()).abs() > EPSILON || r.round() < 0.0 {
+            return Err(GridWriteError::OffGrid(block.position));
+        }
+
+        cells.push((d.round() as i32, r.round() as usize, block));
+    }
+
+    if cells.is_empty() {
+        return Ok("..".to_string());
+    }
+
+    let odd = cells[0].0 % 2 == 0;
+    if let Some((_, _, block)) = cells.iter().find(|(d, _, _)| (d % 2 == 0) != odd) {
+        return Err(GridWriteError::OffGrid(block.position));
+    }
+
+    let cols = cells.iter().map(|(d, _, _)| d.abs() as usize).max().unwrap() + 1;
+    let rows = cells.iter().map(|(_, r, _)| *r).max().unwrap() + 1;
+
+    let mut grid: Vec<Vec<Option<String>>> = vec![vec![None; cols]; rows];
+
+    for (d, r, block) in cells {
+        let c = ((d + cols as i32 - 1) / 2) as usize;
+
+        if grid[r][c].is_some() {
+            return Err(GridWriteError::Occupied(r, c));
+        }
+
+        grid[r][c] = Some(block_to_slot(block)?);
+    }
+
+    let widths: Vec<usize> = (0..cols)
+        .map(|c| grid.iter().filter_map(|row| row[c].as_ref()).map(|s| s.len()).max().unwrap_or(2))
+        .collect();
+
+    let lines: Vec<String> = grid
+        .iter()
+        .map(|row| {
+            let slots: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(slot, width)| format!("{:width$}", slot.as_deref().unwrap_or(".."), width = *width))
+                .collect();
+
+            slots.join(" ").trim_end().to_string()
+        })
+        .collect();
+
+    Ok(lines.join("\n "))
 }
 
 
@@ -193,19 +1054,334 @@ mod tests {
  .. .. .. .. .. .. .. .. .. ..
  AA .. .. .. .. .. .. .. .. AA".to_string();
 
-        if let Some(res) = interpret_grid(&a_level, 10.0) {
-            assert_eq!(res.len(), 5);
+        let res = interpret_grid(&a_level, 10.0, true).unwrap();
+        assert_eq!(res.len(), 5);
 
-            for b in res {
-                println!("{:?}", b);
-            }
-        } else {
-            assert!(false)
+        for b in res {
+            println!("{:?}", b);
+        }
+    }
+
+    #[test]
+    fn reports_unknown_letters() {
+        let a_level =
+"AA AA AA
+ AA XA AA".to_string();
+
+        let err = interpret_grid(&a_level, 2.0, false).unwrap_err();
+        assert_eq!(err.line, 2);
+        assert_eq!(err.column, 5);
+        assert_eq!(err.token, "XA");
+        assert_eq!(err.kind, LayoutErrorKind::UnknownBlockType('X'));
+
+        let err = interpret_grid(&"AA AX".to_string(), 2.0, false).unwrap_err();
+        assert_eq!(err.kind, LayoutErrorKind::UnknownBehaviour('X'));
+        assert_eq!(err.column, 4);
+    }
+
+    #[test]
+    fn reports_bad_slots_and_triggers() {
+        let kind = |layout: &str| interpret_grid(&layout.to_string(), 2.0, false).unwrap_err().kind;
+
+        assert_eq!(kind("AA A AA"), LayoutErrorKind::SlotTooShort);
+        assert_eq!(kind("AA AAA12"), LayoutErrorKind::SlotTooLong);
+        assert_eq!(kind("AIX1"), LayoutErrorKind::UnknownTriggerType('X'));
+        assert_eq!(kind("AIR"), LayoutErrorKind::MissingTriggerGroup);
+        assert_eq!(kind("AIRx"), LayoutErrorKind::InvalidTriggerGroup('x'));
+        assert_eq!(kind("AIR[portal"), LayoutErrorKind::UnclosedTriggerGroup);
+        assert_eq!(kind("AIR[]"), LayoutErrorKind::InvalidTriggerGroupName("".to_string()));
+        assert_eq!(kind("AIR[a-b]"), LayoutErrorKind::InvalidTriggerGroupName("a-b".to_string()));
+        assert_eq!(kind("AIR[70000]"), LayoutErrorKind::InvalidTriggerGroupName("70000".to_string()));
+        assert_eq!(kind("AI[portal]"), LayoutErrorKind::GroupWithoutTrigger);
+        assert_eq!(kind("AIR1[portal]"), LayoutErrorKind::SlotTooLong);
+        assert_eq!(kind("AE{speed=80"), LayoutErrorKind::UnclosedParameters);
+        assert_eq!(kind("AE{speed:80}"), LayoutErrorKind::InvalidParameter("speed:80".to_string()));
+        assert_eq!(kind("AE{speed=-5}"), LayoutErrorKind::InvalidParameter("speed=-5".to_string()));
+        assert_eq!(kind("AC{phase=1.5}"), LayoutErrorKind::InvalidParameter("phase=1.5".to_string()));
+        assert_eq!(kind("AA{speed=80}"), LayoutErrorKind::UnknownParameter("speed".to_string()));
+        assert_eq!(kind("AC{speed=80}"), LayoutErrorKind::UnknownParameter("speed".to_string()));
+        assert_eq!(kind("\n  \n"), LayoutErrorKind::EmptyLayout);
+    }
+
+    #[test]
+    fn reads_block_parameters() {
+        let a_level = "AE{speed=80} AC{period=0.5,phase=0.25} ZCR[door]{period=2} AD{force=100} AC".to_string();
+
+        let layout = interpret_layout(&a_level, 2.0, true).unwrap();
+        let behaviours: Vec<BlockBehaviour> = layout.blocks.iter().map(|b| b.behaviour.clone()).collect();
+
+        assert_eq!(behaviours, vec![
+            BlockBehaviour::EvaderR(80.0),
+            BlockBehaviour::Vanisher(0.5, 0.25),
+            BlockBehaviour::Vanisher(2.0, 0.0),
+            BlockBehaviour::Repuslor(REPULSOR_THRESHOLD, 100.0),
+            BlockBehaviour::Vanisher(VANISHER_PERIOD, 0.0),
+        ]);
+
+        assert_eq!(block_to_slot(&layout.blocks[1]).unwrap(), "AC{period=0.5,phase=0.25}");
+        assert_eq!(block_to_slot(&layout.blocks[4]).unwrap(), "AC");
+
+        let blocks = interpret_grid(&"AB{speed=-45} AB".to_string(), 2.0, false).unwrap();
+        assert_eq!(blocks[0].behaviour, BlockBehaviour::Spinner(-45.0));
+        assert_eq!(block_to_slot(&blocks[0]).unwrap(), "AB{speed=-45}");
+        assert_eq!(block_to_slot(&blocks[1]).unwrap(), "AB");
+
+        let blocks = interpret_grid(&"ZIS1{exit=90} ZIS1".to_string(), 2.0, false).unwrap();
+        assert_eq!(blocks[0].behaviour, BlockBehaviour::Portal(90.0));
+        assert_eq!(block_to_slot(&blocks[0]).unwrap(), "ZIS1{exit=90}");
+        assert_eq!(block_to_slot(&blocks[1]).unwrap(), "ZIS1");
+    }
+
+    #[test]
+    fn reads_explosives_and_regenerators() {
+        let blocks = interpret_grid(&"AJ BJ{radius=35} AJ{radius=0}".to_string(), 2.0, false);
+        assert_eq!(blocks.unwrap_err().kind, LayoutErrorKind::InvalidParameter("radius=0".to_string()));
+
+        let blocks = interpret_grid(&"AJ BJ{radius=35}".to_string(), 2.0, false).unwrap();
+        assert_eq!(blocks[0].behaviour, BlockBehaviour::Explosive(EXPLOSIVE_RADIUS));
+        assert_eq!(blocks[1].behaviour, BlockBehaviour::Explosive(35.0));
+
+        assert_eq!(block_to_slot(&blocks[0]).unwrap(), "AJ");
+        assert_eq!(block_to_slot(&blocks[1]).unwrap(), "BJ{radius=35}");
+
+        let blocks = interpret_grid(&"BL CL{delay=1.5}".to_string(), 2.0, false).unwrap();
+        assert_eq!(blocks[0].behaviour, BlockBehaviour::Regenerating(REGENERATION_DELAY));
+        assert_eq!(block_to_slot(&blocks[1]).unwrap(), "CL{delay=1.5}");
+
+        let blocks = interpret_grid(&"ZM ZM{strength=400,falloff=2}".to_string(), 2.0, false).unwrap();
+        assert_eq!(blocks[0].behaviour, BlockBehaviour::Field(FIELD_RADIUS, FIELD_STRENGTH, FIELD_FALLOFF));
+        assert_eq!(blocks[1].behaviour, BlockBehaviour::Field(FIELD_RADIUS, 400.0, 2.0));
+        assert_eq!(block_to_slot(&blocks[1]).unwrap(), "ZM{strength=400,falloff=2}");
+        assert!(interpret_grid(&"ZM{falloff=-1}".to_string(), 2.0, false).is_err());
+    }
+
+    #[test]
+    fn counts_the_halves_of_splitters() {
+        let blocks = interpret_grid(&"AK BA ZK".to_string(), 2.0, false).unwrap();
+
+        assert_eq!(blocks[0].behaviour, BlockBehaviour::Splitter);
+        assert_eq!(blocks.iter().map(|b| b.block_count()).collect::<Vec<_>>(), vec![3, 1, 0]);
+        assert_eq!(block_to_slot(&blocks[0]).unwrap(), "AK");
+    }
+
+    #[test]
+    fn reads_block_tags() {
+        let a_level = "AA{tag=core} AE{tag=core,speed=80} AA".to_string();
+
+        let blocks = interpret_grid(&a_level, 2.0, true).unwrap();
+        let tags: Vec<Option<&str>> = blocks.iter().map(|b| b.tag.as_deref()).collect();
+
+        assert_eq!(tags, vec![Some("core"), Some("core"), None]);
+        assert_eq!(blocks[1].behaviour, BlockBehaviour::EvaderR(80.0));
+        assert_eq!(block_to_slot(&blocks[1]).unwrap(), "AE{speed=80,tag=core}");
+
+        let kind = |layout: &str| interpret_grid(&layout.to_string(), 2.0, false).unwrap_err().kind;
+
+        assert_eq!(kind("AA{tag=}"), LayoutErrorKind::InvalidParameter("tag=".to_string()));
+        assert_eq!(kind("AA{tag=a-b}"), LayoutErrorKind::InvalidParameter("tag=a-b".to_string()));
+        assert_eq!(kind("ZA{tag=wall}"), LayoutErrorKind::UnknownParameter("tag".to_string()));
+    }
+
+    #[test]
+    fn reads_block_drops() {
+        let a_level = "AA{drop=Grabber(2)} BE{drop=MoreBalls(1),tag=core} AA".to_string();
+
+        let blocks = interpret_grid(&a_level, 2.0, true).unwrap();
+        let drops: Vec<Option<PickupType>> = blocks.iter().map(|b| b.drop).collect();
+
+        assert_eq!(drops, vec![Some(PickupType::Grabber(2)), Some(PickupType::MoreBalls(1)), None]);
+        assert_eq!(block_to_slot(&blocks[1]).unwrap(), "BE{tag=core,drop=MoreBalls(1)}");
+
+        let kind = |layout: &str| interpret_grid(&layout.to_string(), 2.0, false).unwrap_err().kind;
+
+        assert_eq!(kind("AA{drop=Grabber}"), LayoutErrorKind::InvalidParameter("drop=Grabber".to_string()));
+        assert_eq!(kind("AA{drop=Magnet(1)}"), LayoutErrorKind::InvalidParameter("drop=Magnet(1)".to_string()));
+        assert_eq!(kind("AA{drop=MoreBalls(0)}"), LayoutErrorKind::InvalidParameter("drop=MoreBalls(0)".to_string()));
+        assert_eq!(kind("ZA{drop=Grabber(1)}"), LayoutErrorKind::UnknownParameter("drop".to_string()));
+    }
+
+    #[test]
+    fn resolves_named_groups() {
+        let a_level =
+"ZIR[portalA] AAA0      AAA[12] ZIR[12]
+ AAA[portalA] ZIR[door] ..      ZAS1".to_string();
+
+        let layout = interpret_layout(&a_level, 2.0, true).unwrap();
+        let groups: Vec<Option<TriggerGroup>> = layout.blocks.iter().map(|b| b.trigger_group).collect();
+
+        // 0, 1 and 12 are taken by numbered groups
+        assert_eq!(groups, vec![Some(2), Some(0), Some(12), Some(12), Some(2), Some(3), Some(1)]);
+
+        assert_eq!(layout.warnings.len(), 1);
+        assert_eq!(layout.warnings[0].group, "door");
+        assert_eq!((layout.warnings[0].line, layout.warnings[0].column), (2, 15));
+    }
+
+    #[test]
+    fn shares_group_names_between_layouts() {
+        let mut layouts = vec![
+            interpret_layout(&"ZIR[door] AAA0".to_string(), 2.0, true).unwrap(),
+            interpret_layout(&"AAA[door] ZAS2 AAA[lift]".to_string(), 2.0, true).unwrap(),
+        ];
+
+        // On its own the second layout gives door the 0 of the first one
+        assert_eq!(layouts[1].blocks[0].trigger_group, Some(0));
+
+        share_group_names(&mut layouts);
+        let groups: Vec<Option<TriggerGroup>> = layouts.iter().flat_map(|l| l.blocks.iter().map(|b| b.trigger_group)).collect();
+
+        assert_eq!(groups, vec![Some(1), Some(0), Some(1), Some(2), Some(3)]);
+    }
+
+    #[test]
+    fn places_hex_rings_and_freeform_blocks() {
+        let hex = interpret_hex_layout(&"AA AA\nAA AA\nAA AA".to_string(), 2.0, true).unwrap();
+        let grid = interpret_grid(&"AA AA\nAA AA\nAA AA".to_string(), 2.0, true).unwrap();
+
+        assert_eq!(hex.blocks[0].position, grid[0].position);
+        assert_eq!(hex.blocks[2].position.x, grid[2].position.x + (BLOCK_WIDTH + 2.0) / 2.0);
+        assert_eq!(hex.blocks[4].position, grid[4].position);
+
+        let rings = vec![
+            Ring { radius: 10.0, count: 4, slot: "AA".to_string(), ..default() },
+            Ring { radius: 20.0, count: 2, rotation: 90.0, slot: "BA{tag=core}".to_string() },
+        ];
+        let layout = interpret_rings(Vec2::new(0.0, -50.0), &rings).unwrap();
+
+        assert_eq!(layout.blocks.len(), 6);
+        assert!((layout.blocks[1].position - Vec2::new(0.0, -60.0)).length() < 0.001);
+        assert!((layout.blocks[4].position - Vec2::new(0.0, -70.0)).length() < 0.001);
+        assert!((layout.blocks[4].rotation - 0.0).abs() < 0.001);
+        assert_eq!(layout.blocks[5].tag.as_deref(), Some("core"));
+
+        let bad = vec![Ring::default(), Ring { slot: "XA".to_string(), ..default() }];
+        let err = interpret_rings(Vec2::ZERO, &bad).unwrap_err();
+        assert_eq!((err.line, err.kind), (2, LayoutErrorKind::UnknownBlockType('X')));
+
+        let placements = vec![
+            Placement { position: Vec2::new(5.0, -40.0), rotation: 45.0, slot: "AIR[door]".to_string() },
+            Placement { slot: "ZAA[door]".to_string(), ..default() },
+        ];
+        let layout = interpret_freeform(&placements).unwrap();
+
+        assert_eq!(layout.blocks[0].position, Vec2::new(5.0, -40.0));
+        assert!((layout.blocks[0].rotation - 45.0_f32.to_radians()).abs() < 0.001);
+        assert_eq!(layout.blocks[0].trigger_group, layout.blocks[1].trigger_group);
+        assert!(layout.warnings.is_empty());
+    }
+
+    #[test]
+    fn strict_mode_rejects_ragged_rows() {
+        let a_level =
+"AA AA AA
+ AA AA
+ AA AA AA".to_string();
+
+        let err = interpret_grid(&a_level, 2.0, true).unwrap_err();
+        assert_eq!(err.line, 2);
+        assert_eq!(err.kind, LayoutErrorKind::RaggedRow(3, 2));
+
+        let res = interpret_grid(&a_level, 2.0, false).unwrap();
+        assert_eq!(res.len(), 8);
+    }
+
+    #[test]
+    fn ignores_surrounding_blank_lines() {
+        let a_level = "\nAA AA\n AA AA\n".to_string();
+
+        let res = interpret_grid(&a_level, 2.0, true).unwrap();
+        assert_eq!(res.len(), 4);
+        assert_eq!(res[0].position, interpret_grid(&"AA AA".to_string(), 2.0, true).unwrap()[0].position);
+    }
+
+    fn assert_same_blocks(a: &Vec<Block>, b: &Vec<Block>, layout: &str) {
+        assert_eq!(a.len(), b.len(), "{}", layout);
+
+        for (a, b) in a.iter().zip(b) {
+            assert!((a.position - b.position).length() < 0.01, "{}", layout);
+            assert_eq!(a.block_type, b.block_type, "{}", layout);
+            assert_eq!(a.behaviour, b.behaviour, "{}", layout);
+            assert_eq!(a.trigger_type, b.trigger_type, "{}", layout);
+            assert_eq!(a.trigger_group, b.trigger_group, "{}", layout);
+            assert_eq!(a.tag, b.tag, "{}", layout);
+            assert_eq!(a.drop, b.drop, "{}", layout);
         }
+    }
 
+    #[test]
+    fn serializes_a_grid() {
+        let a_level =
+"ZIR1 .. .. CA .. .. ZAA1
+ ..   .. .. CA .. .. ..".to_string();
 
+        let blocks = interpret_grid(&a_level, 2.0, true).unwrap();
 
+        assert_eq!(serialize_grid(&blocks, 2.0).unwrap(), a_level);
     }
 
+    #[test]
+    fn parse_serialize_parse_is_identity() {
+        use rand::{Rng, SeedableRng};
+        use rand::rngs::StdRng;
+
+        let types = ['A', 'B', 'C', 'D', 'Z'];
+        let behaviours = ['A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I'];
+        let triggers = ['A', 'B', 'C', 'R', 'S'];
+
+        let mut rng = StdRng::seed_from_u64(4711);
+
+        for _ in 0..500 {
+            let rows = rng.gen_range(1..8);
+            let cols = rng.gen_range(1..12);
+            let gap = [0.0, 2.0, 10.0][rng.gen_range(0..3)];
 
-}
\ No newline at end of file
+            let mut lines = vec![];
+            for _ in 0..rows {
+                let mut slots = vec![];
+                for _ in 0..cols {
+                    if rng.gen_bool(0.3) {
+                        slots.push("..".to_string());
+                        continue;
+                    }
+
+                    let mut slot = String::new();
+                    slot.push(types[rng.gen_range(0..types.len())]);
+                    slot.push(behaviours[rng.gen_range(0..behaviours.len())]);
+
+                    let params = match slot.chars().last().unwrap() {
+                        _ if rng.gen_bool(0.7) => String::new(),
+                        'C' => format!("{{period={},phase={}}}", rng.gen_range(1..40) as f32 / 8.0, rng.gen_range(0..4) as f32 / 4.0),
+                        'D' => format!("{{threshold={}}}", rng.gen_range(5..40)),
+                        'E' | 'F' | 'G' | 'H' => format!("{{speed={}}}", rng.gen_range(10..120)),
+                        _ => String::new(),
+                    };
+
+                    if rng.gen_bool(0.2) {
+                        slot.push(triggers[rng.gen_range(0..triggers.len())]);
+                        if rng.gen_bool(0.5) {
+                            slot.push(char::from_digit(rng.gen_range(0..10), 10).unwrap());
+                        } else {
+                            slot.push_str(&format!("[{}]", rng.gen_range(0..300)));
+                        }
+                    }
+
+                    slot.push_str(&params);
+                    slots.push(slot);
+                }
+                lines.push(slots.join(" "));
+            }
+
+            let layout = lines.join("\n ");
+            let parsed = match interpret_grid(&layout, gap, true) {
+                Ok(blocks) => blocks,
+                Err(LayoutError { kind: LayoutErrorKind::EmptyLayout, .. }) => continue,
+                Err(e) => panic!("{}: {}", layout, e),
+            };
+
+            let written = serialize_grid(&parsed, gap).unwrap();
+            let reparsed = interpret_grid(&written, gap, true).unwrap();
+
+            assert_same_blocks(&parsed, &reparsed, &layout);
+        }
+    }
+}
@@ -1,34 +1,61 @@
-use std::time::Duration;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
 
 use bevy::app::App;
 use bevy::log::{error, info, warn};
-use bevy::math::Vec2;
-use bevy::prelude::{Commands, Component, IntoSystemDescriptor, Plugin, ResMut, Resource, SystemSet, Vec3};
+use bevy::prelude::{Commands, Component, EventReader, EventWriter, IntoSystemDescriptor, Local, Plugin, Res, ResMut, Resource, State, SystemSet, Vec2, Vec3};
+use bevy::time::FixedTimestep;
 use bevy::utils::{default, HashMap};
 use rand::{Rng, thread_rng};
+use serde::{Deserialize, Serialize};
 
 use crate::block::{Block, BlockBehaviour, BlockType};
-use crate::config::{ARENA_WIDTH_H, BLOCK_GAP, BLOCK_WIDTH};
+use crate::block::trigger::{TriggerGroup, TriggerLogic};
+use crate::config::{BLOCK_GAP, LEVEL_HOT_RELOAD_ENABLED};
 use crate::labels::SystemLabels;
-use crate::level::layout::{generate_block_grid, interpret_grid};
-use crate::level::TargetLayout::{FilledGrid, SparseGrid};
+use crate::level::campaign::load_level;
+use crate::level::conveyor::{ConveyorLane, ConveyorPlugin};
+use crate::level::custom::CustomLayouts;
+use crate::level::distribution::pickup_slots;
+use crate::level::layers::BlockLayer;
+use crate::level::layout::{generate_block_grid, interpret_freeform, interpret_hex_layout, interpret_layout, interpret_rings, Layout, LayoutError, Placement, Ring, share_group_names};
+use crate::level::TargetLayout::{FilledGrid, Freeform, HexGrid, Radial, SparseGrid};
 use crate::pickups::PickupType;
 use crate::r#match::state::MatchState;
 use crate::ship::Ship;
 use crate::state::GameState;
 
-mod layout;
+pub mod layout;
+pub mod campaign;
+pub mod generator;
+pub mod custom;
+pub mod conveyor;
+pub mod criteria;
+pub mod distribution;
+pub mod layers;
+
+pub use criteria::WinCriteria;
+pub use distribution::PickupSpacing;
 
 #[derive(Component)]
 pub struct RequestTag;
 
 
+#[derive(Serialize, Deserialize)]
 pub enum TargetLayout {
     FilledGrid(usize, usize, BlockType, BlockBehaviour, f32),
     SparseGrid(String, f32),
+    // SparseGrid with every second row shifted by half a block
+    HexGrid(String, f32),
+    // Rings of blocks around a center position (x, z)
+    Radial(Vec2, Vec<Ring>),
+    // Blocks at arbitrary positions and rotations
+    Freeform(Vec<Placement>),
     Custom(String)
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub enum LevelObstacle {
     // Center position, width, height
     Box(Vec3, f32, f32),
@@ -36,23 +63,44 @@ pub enum LevelObstacle {
     // Center position, Normal, width, flip normal when rotating in place (hacky)
     ForceField(Vec3, Vec3, f32, bool),
 
-    DirectionalDeathTrigger(Vec3, Vec3, f32)
-}
+    DirectionalDeathTrigger(Vec3, Vec3, f32),
+
+    // Box moving in a loop through its waypoints: waypoints, width, height, speed.
+    // With a trigger group it only moves while the group is started.
+    PathBox(Vec<Vec3>, f32, f32, f32, Option<TriggerGroup>),
 
-pub enum WinCriteria {
-    BlockHitPercentage(f32)
+    // Box rotating around a pivot: pivot, center of the box relative to the pivot, width, height,
+    // radians per second (counter clockwise seen from above), trigger group like PathBox
+    RotatingBox(Vec3, Vec3, f32, f32, f32, Option<TriggerGroup>),
+
+    // Moves balls passing through between two block layers: center position, width, depth,
+    // the two layers
+    Lift(Vec3, f32, f32, i32, i32),
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct LevelDefinition {
     pub background_asset: String,
     pub background_scroll_velocity: f32,
     pub simultaneous_balls: i32,
     pub win_criteria: WinCriteria,
     pub targets: TargetLayout,
+    // Blocks stacked above or below the targets
+    pub layers: Vec<BlockLayer>,
+    #[serde(with = "campaign::optional_seconds")]
     pub time_limit: Option<Duration>,
     pub global_pickups: Vec<PickupType>,
+    pub pickup_spacing: PickupSpacing,
+    // Seed for the pickup slots, a new one is drawn every match if not set
+    pub pickup_seed: Option<u64>,
+    #[serde(skip)]
     pub distributed_global_pickups: HashMap<usize, PickupType>,
     pub obstacles: Vec<LevelObstacle>,
+    // Lanes of the Conveyor layout
+    pub conveyor: Vec<ConveyorLane>,
+    // Gates between trigger groups
+    pub triggers: Vec<TriggerLogic>,
     pub default_wall_l: bool,
     pub default_wall_r: bool,
 
@@ -66,10 +114,15 @@ impl Default for LevelDefinition {
             simultaneous_balls: 1,
             win_criteria: WinCriteria::BlockHitPercentage(1.0),
             targets: FilledGrid(5, 5, BlockType::Simple, BlockBehaviour::SittingDuck, BLOCK_GAP),
+            layers: vec![],
             time_limit: None,
             global_pickups: vec![],
+            pickup_spacing: PickupSpacing::Uniform,
+            pickup_seed: None,
             distributed_global_pickups: Default::default(),
             obstacles: vec![],
+            conveyor: vec![],
+            triggers: vec![],
             default_wall_l: true,
             default_wall_r: true
         }
@@ -77,9 +130,15 @@ impl Default for LevelDefinition {
 }
 
 
+// Sent when the current level's file changed on disk and its definition was replaced
+pub struct LevelReloaded;
+
+
 #[derive(Resource)]
 pub struct Levels {
     pub definitions: Vec<LevelDefinition>,
+    // File each definition was loaded from, used for hot reloading
+    pub sources: Vec<PathBuf>,
     pub current_level: usize
 }
 
@@ -114,41 +173,17 @@ impl LevelDefinition {
         self.distributed_global_pickups.clear();
     }
 
-    pub fn distribute_global_pickups(&mut self, block_count: usize) {
-        let mut rng = thread_rng();
+    pub fn distribute_global_pickups(&mut self, block_count: usize, seed: u64) {
         self.distributed_global_pickups.clear();
 
+        let slots = pickup_slots(self.global_pickups.len(), block_count, self.pickup_spacing, seed);
 
-        let mut placed: Vec<usize> = vec![];
-        let mut start = 0;
-        let mut end = block_count;
-        //info!("Distributing {} global pickups", self.global_pickups.len());
-        for pickup in &self.global_pickups {
-            let mut repeats = 10;
-
-            while repeats > 0 {
-                repeats -= 1;
-                let pos: usize = rng.gen_range(start..end);
-
-                if placed.contains(&pos) {
-                    warn!("Moving start and end around should avoid this!");
-                } else {
-                    if pos == block_count - 1 {
-                        end = pos + 1;
-                    } else {
-                        start = pos;
-                    }
-                    placed.push(pos);
-                    self.distributed_global_pickups.insert(pos, pickup.clone());
-
-                    //info!("{:?} at {}", pickup, pos);
-                    break;
-                }
-            }
+        if slots.len() < self.global_pickups.len() {
+            warn!("Only {} of {} global pickups fit into {} blocks", slots.len(), self.global_pickups.len(), block_count);
+        }
 
-            if repeats == 0 {
-                warn!("Could not distribute all pickups")
-            }
+        for (slot, pickup) in slots.into_iter().zip(&self.global_pickups) {
+            self.distributed_global_pickups.insert(slot, pickup.clone());
         }
     }
 }
@@ -158,139 +193,203 @@ pub struct LevelPlugin;
 impl Plugin for LevelPlugin {
     fn build(&self, app: &mut App) {
         app
+            .add_event::<LevelReloaded>()
+            .add_plugin(ConveyorPlugin)
+
             .add_system_set(
                 SystemSet::on_enter(GameState::InMatch)
                     .with_system(level_spawn.label(SystemLabels::UpdateWorld))
             )
 
+            .add_system_set(
+                SystemSet::on_update(GameState::InMatch)
+                    .with_system(level_respawn_on_reload.after(SystemLabels::LevelReload))
+            )
         ;
+
+        if LEVEL_HOT_RELOAD_ENABLED {
+            // The timestep replaces the state's run criteria, level_watch_file checks the state itself
+            app.add_system_set(
+                SystemSet::new()
+                    .with_system(level_watch_file)
+                    .with_run_criteria(FixedTimestep::step(0.5))
+                    .label(SystemLabels::LevelReload)
+            );
+        }
     }
 }
 
 
-fn make_filled_grid(
-    mut commands: &mut Commands,
-    cols: usize, rows: usize, block_type: &BlockType, behaviour: &BlockBehaviour, gap: f32) -> i32
-{
-    let positions = generate_block_grid(rows, cols, gap);
-
-    for i in 0..positions.len() {
-        let pos = positions.get(i).unwrap();
-
-        commands.
-            spawn(Block {
-                position: pos.clone(),
-                behaviour: behaviour.clone(),
-                block_type: block_type.clone(),
-                ..default()
+// Blocks of every layout but Custom, whose builders spawn their own
+fn target_layout(targets: &TargetLayout) -> Result<Layout, LayoutError> {
+    match targets {
+        FilledGrid(cols, rows, block_type, behaviour, gap) => {
+            Ok(Layout {
+                blocks: generate_block_grid(*rows, *cols, *gap)
+                    .into_iter()
+                    .map(|position| Block {
+                        position,
+                        behaviour: behaviour.clone(),
+                        block_type: block_type.clone(),
+                        ..default()
+                    })
+                    .collect(),
+                warnings: vec![],
+                names: vec![],
             })
-            .insert(RequestTag);
-    }
+        }
 
-    positions.len() as i32
+        SparseGrid(layout, gap) => interpret_layout(layout, *gap, false),
+        HexGrid(layout, gap) => interpret_hex_layout(layout, *gap, false),
+        Radial(center, rings) => interpret_rings(*center, rings),
+        Freeform(placements) => interpret_freeform(placements),
+        TargetLayout::Custom(_) => Ok(Layout { blocks: vec![], warnings: vec![], names: vec![] }),
+    }
 }
 
-fn make_grid_from_string_layout(
-    mut commands: &mut Commands,
-    layout: &String,
-    gap: f32,
+fn make_blocks(
+    commands: &mut Commands,
+    blocks: Vec<Block>,
+    layer: i32,
 ) -> i32 {
-    if let Some(res) = interpret_grid(layout, gap) {
-        let mut c = 0;
-        for b in res {
-            if b.block_type != BlockType::Obstacle {
-                c += 1;
-            }
-
-            commands
-                .spawn(b)
-                .insert(RequestTag);
+    let mut c = 0;
+    for mut b in blocks {
+        c += b.block_count();
 
-        }
+        b.layer = layer;
+        commands
+            .spawn(b)
+            .insert(RequestTag);
 
-        return c;
     }
 
-    0
+    c
 }
 
 fn level_spawn(
     mut stats: ResMut<MatchState>,
     mut levels: ResMut<Levels>,
+    layouts: Res<CustomLayouts>,
     mut commands: Commands) {
     commands
         .spawn(Ship::default())
         .insert(RequestTag);
 
 
-    let mut level = levels.get_current_level_mut().unwrap();
+    let level = levels.get_current_level_mut().unwrap();
+    level_spawn_targets(&mut stats, level, &layouts, &mut commands);
+}
 
-    match &level.targets {
-        FilledGrid(cols, rows, block_type, behaviour, gap) => {
-            let count = make_filled_grid(&mut commands, *cols, *rows, block_type, behaviour, *gap);
-            level.distribute_global_pickups(count as usize);
-            stats.set_block_count(count);
+fn level_spawn_targets(
+    stats: &mut MatchState,
+    level: &mut LevelDefinition,
+    layouts: &CustomLayouts,
+    commands: &mut Commands,
+) {
+    // Logged, so a match can be played again with the same pickups by putting it into the level
+    stats.seed = level.pickup_seed.unwrap_or_else(|| thread_rng().gen());
+    info!("Pickup seed {}", stats.seed);
 
-        }
+    match &level.targets {
+        TargetLayout::Custom(name) => {
+            // Unknown names are rejected when loading, this only happens for levels changed in memory
+            let Some(builder) = layouts.get(name) else {
+                error!("Unknown custom level definition {}", name);
+                return;
+            };
 
-        SparseGrid(layout, gap) => {
-            let count = make_grid_from_string_layout(&mut commands, layout, *gap);
-            level.distribute_global_pickups(count as usize);
+            let count = builder.build(commands, level, stats);
             stats.set_block_count(count);
         }
 
-        TargetLayout::Custom(name) => {
+        targets => {
+            let mut layers = vec![];
+            let mut layouts = vec![];
 
-            match name.as_str() {
-                "Conveyor" => {
-                    level_span_conveyor(stats, level, commands);
-                }
+            let all = std::iter::once((0, targets)).chain(level.layers.iter().map(|l| (l.layer, &l.targets)));
+            for (layer, targets) in all {
+                match target_layout(targets) {
+                    Ok(layout) => {
+                        layers.push(layer);
+                        layouts.push(layout);
+                    }
 
-                _ => {
-                    error!("Unknown custom level definition {}", name);
+                    Err(e) => error!("Invalid layout: {}", e),
                 }
-            };
+            }
+
+            // A group name stands for the same group in every layer
+            share_group_names(&mut layouts);
 
+            let mut count = 0;
+            for (layer, layout) in layers.into_iter().zip(layouts) {
+                count += make_blocks(commands, layout.blocks, layer);
+            }
+
+            level.distribute_global_pickups(count as usize, stats.seed);
+            stats.set_block_count(count);
         }
     };
 
 }
 
-fn level_span_conveyor(
-    mut stats: ResMut<MatchState>,
-    mut level: &mut LevelDefinition,
-    mut commands: Commands
+fn level_watch_file(
+    state: Res<State<GameState>>,
+    mut levels: ResMut<Levels>,
+    layouts: Res<CustomLayouts>,
+    mut last_seen: Local<Option<(usize, SystemTime)>>,
+    mut events: EventWriter<LevelReloaded>,
 ) {
-    let speed = 10.0;
-    let count_per_row = 2;
-    let mut pos = Vec2::new(ARENA_WIDTH_H + 3.0, -25.0);
-    for i in 0..count_per_row {
-        commands.
-            spawn(Block {
-                position: pos.clone(),
-                behaviour: BlockBehaviour::EvaderL(speed),
-                block_type: BlockType::Simple,
-                ..default()
-            })
-            .insert(RequestTag);
-        pos.x += 2.0 * BLOCK_WIDTH + BLOCK_GAP;
+    // Levels open in the editor are not reloaded under its feet, and what it saved is not
+    // picked up as a change once the match starts
+    if state.current() != &GameState::InMatch {
+        *last_seen = None;
+        return;
     }
 
-    let mut pos = Vec2::new(-ARENA_WIDTH_H - 3.0, -35.0);
-    for i in 0..count_per_row {
-        commands.
-            spawn(Block {
-                position: pos.clone(),
-                behaviour: BlockBehaviour::EvaderR(speed),
-                block_type: BlockType::Simple,
-                ..default()
-            })
-            .insert(RequestTag);
-        pos.x -= 2.0 * BLOCK_WIDTH + BLOCK_GAP;
+    let current = levels.current_level;
+    let Some(path) = levels.sources.get(current).cloned() else { return; };
+    let Ok(modified) = fs::metadata(&path).and_then(|m| m.modified()) else { return; };
+
+    match *last_seen {
+        Some((level, seen)) if level == current && seen != modified => {}
+
+        _ => {
+            // First look at this level's file or nothing changed
+            *last_seen = Some((current, modified));
+            return;
+        }
+    }
+
+    *last_seen = Some((current, modified));
+
+    match load_level(&path, current, &layouts) {
+        Ok(definition) => {
+            info!("Reloading {}", path.display());
+            levels.definitions[current] = definition;
+            events.send(LevelReloaded);
+        }
+
+        Err(e) => error!("Keeping current level: {}", e)
+    }
+}
+
+fn level_respawn_on_reload(
+    mut events: EventReader<LevelReloaded>,
+    mut stats: ResMut<MatchState>,
+    mut levels: ResMut<Levels>,
+    layouts: Res<CustomLayouts>,
+    mut commands: Commands,
+) {
+    if events.iter().count() == 0 {
+        return;
     }
 
-    level.clear_pickups();
-    stats.set_block_count(2 * count_per_row);
+    // The respawned blocks register their tags again
+    stats.tagged.clear();
+
+    let level = levels.get_current_level_mut().unwrap();
+    level_spawn_targets(&mut stats, level, &layouts, &mut commands);
 }
 
 
@@ -6,7 +6,7 @@ use bevy::core_pipeline::bloom::BloomSettings;
 use bevy::core_pipeline::fxaa::Fxaa;
 use bevy::log::info;
 use bevy::pbr::{MaterialMeshBundle, NotShadowReceiver, PbrBundle, StandardMaterial};
-use bevy::prelude::{AmbientLight, Assets, Camera, Camera3dBundle, Color, Commands, Component, default, DirectionalLight, DirectionalLightBundle, Entity, EventWriter, GamepadButtonType, IntoSystemDescriptor, MaterialPlugin, Mesh, OrthographicProjection, Plugin, Quat, Query, Res, ResMut, shape, SystemSet, Transform, Vec3, With};
+use bevy::prelude::{AmbientLight, Time, Assets, Camera, Camera3dBundle, Color, Commands, Component, default, DirectionalLight, DirectionalLightBundle, Entity, EventWriter, GamepadButtonType, IntoSystemDescriptor, MaterialPlugin, Mesh, OrthographicProjection, Plugin, Quat, Query, Res, ResMut, shape, SystemSet, Transform, Vec3, With};
 use leafwing_input_manager::InputManagerBundle;
 use leafwing_input_manager::prelude::{ActionState, InputMap};
 use crate::actions::CameraActions;
@@ -47,6 +47,7 @@ impl Plugin for MatchPlugin {
             .add_system_set(
                 SystemSet::on_update(GameState::InMatch)
                     .with_system(camera_update_position)
+                    .with_system(match_update_clock.before(SystemLabels::UpdateState))
                     // .with_system(camera_follow_ship)
             )
 
@@ -82,6 +83,25 @@ fn match_spawn(
     commands.spawn(Match);
 }
 
+// The clock only runs while a ball is in play, leaving InMatch pauses it
+fn match_update_clock(
+    mut match_state: ResMut<MatchState>,
+    players: Query<&Player>,
+    levels: Res<Levels>,
+    time: Res<Time>,
+    mut game_flow: EventWriter<GameFlowEvent>,
+) {
+    let Some(level) = levels.get_current_level() else { return; };
+
+    if !players.iter().any(|player| player.balls_in_play > 0) {
+        return;
+    }
+
+    if match_state.advance_clock(time.delta(), level.time_limit) {
+        game_flow.send(GameFlowEvent::PlayerLooses);
+    }
+}
+
 fn match_despawn(mut commands: Commands, matches: Query<Entity, With<Match>>) {
     for the_match in &matches {
         //info!("Despawn match {:?}", the_match);
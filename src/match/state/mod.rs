@@ -1,8 +1,18 @@
 use std::time::Duration;
 use bevy::prelude::Resource;
+use bevy::utils::HashMap;
 use crate::block::{BlockBehaviour, BlockType};
+use crate::config::TIME_BONUS_PER_SECOND;
 
 
+// Blocks carrying a tag, for win criteria that refer to it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TaggedBlocks {
+    pub total: i32,
+    pub destroyed: i32,
+    pub lost: i32,
+}
+
 pub enum BlockHitType {
     Regular,
     DirectHit
@@ -46,7 +56,12 @@ pub struct MatchState {
     pub time_taken: Duration,
 
     pub blocks_hit: i32,
-    pub blocks_lost: i32
+    pub blocks_lost: i32,
+
+    pub tagged: HashMap<String, TaggedBlocks>,
+
+    // Seed the level's pickups were distributed with
+    pub seed: u64,
 }
 
 
@@ -67,6 +82,7 @@ impl MatchState {
         self.balls = 0;
         self.blocks_hit = 0;
         self.blocks_lost = 0;
+        self.tagged.clear();
     }
 }
 
@@ -88,6 +104,8 @@ impl Default for MatchState {
             paddle_bounce_combo_possible: false,
             balls: 0,
             blocks_lost: 0,
+            tagged: Default::default(),
+            seed: 0,
         }
     }
 }
@@ -127,11 +145,15 @@ impl MatchState {
 
         base_points += match behaviour {
             BlockBehaviour::SittingDuck => 0,
-            BlockBehaviour::Spinner => 50,
-            BlockBehaviour::Vanisher => 100,
-            BlockBehaviour::Repuslor => 150,
+            BlockBehaviour::Spinner(_) => 50,
+            BlockBehaviour::Vanisher(_, _) => 100,
+            BlockBehaviour::Repuslor(_, _) => 150,
+            BlockBehaviour::Field(_, _, _) => 150,
             BlockBehaviour::EvaderR(_) | BlockBehaviour::EvaderL(_) | BlockBehaviour::EvaderD(_) | BlockBehaviour::EvaderU(_) => 150,
-            BlockBehaviour:: Portal => 0
+            BlockBehaviour::Portal(_) => 0,
+            BlockBehaviour::Explosive(_) => 100,
+            BlockBehaviour::Splitter => 50,
+            BlockBehaviour::Regenerating(_) => 100,
         };
 
         base_points
@@ -191,6 +213,18 @@ impl MatchState {
         self.blocks -= 1;
     }
 
+    pub fn add_tagged_block(&mut self, tag: &str) {
+        self.tagged.entry(tag.to_string()).or_default().total += 1;
+    }
+
+    pub fn tagged_block_destroyed(&mut self, tag: &str) {
+        self.tagged.entry(tag.to_string()).or_default().destroyed += 1;
+    }
+
+    pub fn tagged_block_lost(&mut self, tag: &str) {
+        self.tagged.entry(tag.to_string()).or_default().lost += 1;
+    }
+
     pub fn set_block_count(&mut self, count: i32) {
         self.blocks = count;
     }
@@ -198,6 +232,33 @@ impl MatchState {
     pub fn set_ball_count(&mut self, count: i32) {
         self.balls = count;
     }
+
+    // Returns true only in the update that used up the time limit
+    pub fn advance_clock(&mut self, delta: Duration, time_limit: Option<Duration>) -> bool {
+        let was_running = !self.time_is_up(time_limit);
+        self.time_taken += delta;
+
+        was_running && self.time_is_up(time_limit)
+    }
+
+    pub fn time_is_up(&self, time_limit: Option<Duration>) -> bool {
+        match time_limit {
+            Some(limit) => self.time_taken >= limit,
+            None => false,
+        }
+    }
+
+    pub fn time_remaining(&self, time_limit: Option<Duration>) -> Option<Duration> {
+        time_limit.map(|limit| limit.saturating_sub(self.time_taken))
+    }
+
+    // Every full second left on the clock is worth some points
+    pub fn time_bonus(&self, time_limit: Option<Duration>) -> i32 {
+        match self.time_remaining(time_limit) {
+            Some(remaining) => remaining.as_secs() as i32 * TIME_BONUS_PER_SECOND,
+            None => 0,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -291,6 +352,22 @@ mod tests {
         assert_eq!(s.paddle_bounce_combo, 1);
     }
 
+    #[test]
+    fn runs_out_of_time_once() {
+        let mut s = MatchState::default();
+        let limit = Some(Duration::from_secs(10));
+
+        assert!(!s.advance_clock(Duration::from_secs(6), limit));
+        assert_eq!(s.time_remaining(limit), Some(Duration::from_secs(4)));
+        assert_eq!(s.time_bonus(limit), 4 * TIME_BONUS_PER_SECOND);
 
+        assert!(s.advance_clock(Duration::from_secs(5), limit));
+        assert!(!s.advance_clock(Duration::from_secs(1), limit));
+        assert!(s.time_is_up(limit));
+        assert_eq!(s.time_remaining(limit), Some(Duration::ZERO));
+        assert_eq!(s.time_bonus(limit), 0);
 
-}
\ No newline at end of file
+        assert!(!s.advance_clock(Duration::from_secs(100), None));
+        assert_eq!(s.time_bonus(None), 0);
+    }
+}
@@ -9,12 +9,16 @@ use bevy_hanabi::{BillboardModifier, ColorOverLifetimeModifier, EffectAsset, Gra
 
 use crate::ball::Ball;
 use crate::block::{Block, Hittable};
+use crate::events::MatchEvent;
 use crate::physics::{CollidableKind, Collision, COLLISION_EVENT_HANDLING, CollisionInfo, CollisionTag};
 use crate::state::GameState;
 
 #[derive(Component)]
 struct ImpactEffect;
 
+#[derive(Component)]
+struct ExplosionEffect;
+
 #[derive(Component)]
 struct TrailEffect;
 
@@ -32,10 +36,16 @@ impl Plugin for ParticlePlugin {
             .add_system_set(
                 SystemSet::on_enter(GameState::InMatch)
                     .with_system(particles_setup_block_impact)
+                    .with_system(particles_setup_explosion)
             )
 
             .add_system_to_stage(COLLISION_EVENT_HANDLING, particle_handle_block_ball)
 
+            .add_system_set(
+                SystemSet::on_update(GameState::InMatch)
+                    .with_system(particle_handle_explosions)
+            )
+
             .add_system_set(
                 SystemSet::on_exit(GameState::InMatch)
                     .with_system(particles_despawn_all)
@@ -82,6 +92,59 @@ fn particles_setup_block_impact(
 }
 
 
+// The impact burst, larger and spreading as far as the explosion reaches
+fn particles_setup_explosion(
+    mut commands: Commands,
+    mut effects: ResMut<Assets<EffectAsset>>,
+) {
+    let mut gradient = Gradient::new();
+    gradient.add_key(0.0, Vec4::new(1.0, 1.0, 1.0, 1.0));
+    gradient.add_key(0.3, Vec4::new(1.0, 0.5, 0.0, 1.0));
+    gradient.add_key(1.0, Vec4::new(0.3, 0.0, 0.0, 0.0));
+
+    let spawner = Spawner::once(80.0.into(), false);
+    let effect = effects.add(
+        EffectAsset {
+            name: "BlockExplosion".into(),
+            capacity: 32768,
+            spawner,
+            ..Default::default()
+        }
+            .init(PositionSphereModifier {
+                radius: 1.0,
+                speed: 40.0.into(),
+                dimension: ShapeDimension::Volume,
+                ..Default::default()
+            })
+            .render(SizeOverLifetimeModifier {
+                gradient: Gradient::constant(Vec2::new(2.0, 1.0)),
+            })
+            .render(ColorOverLifetimeModifier { gradient })
+            .render(BillboardModifier {})
+    );
+
+    commands
+        .spawn(ParticleEffectBundle::new(effect).with_spawner(spawner))
+        .insert(Name::new("explosion effect"))
+        .insert(ExplosionEffect);
+}
+
+
+fn particle_handle_explosions(
+    mut events: EventReader<MatchEvent>,
+    mut effect: Query<(&mut ParticleEffect, &mut Transform), With<ExplosionEffect>>,
+) {
+    let Ok((mut effect, mut effect_transform)) = effect.get_single_mut() else { return; };
+
+    for ev in events.iter() {
+        if let MatchEvent::BlockExploded(position, _) = ev {
+            effect_transform.translation = *position;
+            effect.maybe_spawner().unwrap().reset();
+        }
+    }
+}
+
+
 fn particle_handle_block_ball(
     blocks: Query<(Entity), (With<Block>, With<CollisionTag>, With<Hittable>)>,
     mut effect: Query<(&mut ParticleEffect, &mut Transform), (Without<Block>, With<ImpactEffect>)>,
@@ -155,7 +218,7 @@ fn particle_handle_ball_trail(
 
 fn particles_despawn_all(
     mut commands: Commands,
-    effects: Query<Entity, With<ImpactEffect>>,
+    effects: Query<Entity, Or<(With<ImpactEffect>, With<ExplosionEffect>)>>,
 ) {
     for effect in &effects {
         //info!("Despawn particle effect");
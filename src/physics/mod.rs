@@ -18,6 +18,8 @@ pub enum CollidableKind {
     Wall,
     DeathTrigger,
     DirectionalDeathTrigger(Vec3),
+    // The two layers a lift connects
+    Lift(i32, i32),
     Ship,
     Block,
     Pickup,
@@ -6,6 +6,7 @@ use bevy::scene::SceneBundle;
 use bevy::utils::default;
 use bevy_rapier3d::dynamics::GravityScale;
 use bevy_rapier3d::prelude::{ActiveEvents, Collider, CollisionGroups, RigidBody};
+use serde::{Deserialize, Serialize};
 
 use crate::config::{COLLIDER_GROUP_DEATH, COLLIDER_GROUP_PADDLE, COLLIDER_GROUP_PICKUP, PICKUP_GENERIC_SCENE, PICKUP_SPEED};
 use crate::events::MatchEvent;
@@ -25,7 +26,7 @@ impl Plugin for PickupsPlugin {
                 SystemSet::on_update(GameState::InMatch)
                     .with_system(pickup_spawn.label(SystemLabels::UpdateWorld))
                     .with_system(pickup_update.label(SystemLabels::UpdateWorld))
-                    .with_system(pickup_spawn_globals_on_event.label(SystemLabels::UpdateWorld))
+                    .with_system(pickup_spawn_globals_on_event.label(SystemLabels::UpdateWorld).after(SystemLabels::BlockExplosions))
             )
 
             .add_system_to_stage(COLLISION_EVENT_HANDLING, pickup_handle_collisions)
@@ -39,7 +40,7 @@ impl Plugin for PickupsPlugin {
 }
 
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum PickupType {
     MoreBalls(i32),
     Grabber(i16),
@@ -68,10 +69,23 @@ fn pickup_spawn_globals_on_event(
 
     let level = levels.get_current_level().unwrap();
 
+    // The events are counted in MatchState after this system ran, slots refer to the blocks left after a hit
+    let mut remaining = match_state.blocks;
+
     for ev in events.iter() {
         match ev {
-            MatchEvent::BlockHit(p, block_type, behaviour) => {
-                if let Some(pickup_type) = level.pickup_at(match_state.blocks as usize) {
+            MatchEvent::BlockLost(_) => {
+                remaining -= 1;
+            }
+
+            MatchEvent::BlockHit(p, block_type, behaviour, _) => {
+                remaining -= 1;
+
+                if remaining < 0 {
+                    continue;
+                }
+
+                if let Some(pickup_type) = level.pickup_at(remaining as usize) {
                     commands.spawn(Pickup {
                         spawn_position: p.clone(),
                         pickup_type: pickup_type.clone(),
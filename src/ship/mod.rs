@@ -14,7 +14,7 @@ use leafwing_input_manager::prelude::{ActionState, DualAxis, InputMap};
 
 use crate::actions::MatchActions;
 use crate::ball::{ActiveBall, Ball};
-use crate::config::{ARENA_HEIGHT_H, ARENA_WIDTH_H, BALL_RADIUS, COLLIDER_GROUP_BALL, COLLIDER_GROUP_PADDLE, COLLIDER_GROUP_PICKUP, GRAB_ATTRACT_RADIUS, GRAB_FORCE_MAGNITUDE, GRAB_RADIUS, PADDLE_LIFT, PADDLE_POSITION_MAX_ACCEL, PADDLE_RESTING_ROTATION, PADDLE_RESTING_X, PADDLE_RESTING_Y, PADDLE_RESTING_Z, PADDLE_ROTATION_ACCEL, PADDLE_THICKNESS, PADDLE_WIDTH_H};
+use crate::config::{ARENA_HEIGHT_H, ARENA_WIDTH_H, BALL_RADIUS, COLLIDER_GROUP_BALL, COLLIDER_GROUP_PADDLE, COLLIDER_GROUP_PICKUP, GRAB_ATTRACT_RADIUS, GRAB_FORCE_MAGNITUDE, GRAB_RADIUS, LAYER_HEIGHT, MAX_LAYER, PADDLE_LIFT, PADDLE_POSITION_MAX_ACCEL, PADDLE_RESTING_ROTATION, PADDLE_RESTING_X, PADDLE_RESTING_Y, PADDLE_RESTING_Z, PADDLE_ROTATION_ACCEL, PADDLE_THICKNESS, PADDLE_WIDTH_H};
 use crate::events::MatchEvent;
 use crate::labels::SystemLabels;
 use crate::level::RequestTag;
@@ -120,7 +120,9 @@ fn ship_spawn(
                     .build(),
             })
             .insert(TransformBundle::from(Transform::from_xyz(PADDLE_RESTING_X, PADDLE_RESTING_Y, PADDLE_RESTING_Z)))
-            .insert(Collider::round_cuboid(PADDLE_WIDTH_H - PADDLE_THICKNESS * 0.15, PADDLE_THICKNESS * 0.25, PADDLE_THICKNESS * 0.35, PADDLE_THICKNESS * 0.15))
+            // Reaches every layer the ship can be elevated to, the ball is moved to the ship's
+            // layer when it bounces off
+            .insert(Collider::round_cuboid(PADDLE_WIDTH_H - PADDLE_THICKNESS * 0.15, PADDLE_THICKNESS * 0.25 + 2.0 * MAX_LAYER as f32 * LAYER_HEIGHT, PADDLE_THICKNESS * 0.35, PADDLE_THICKNESS * 0.15))
             .insert(CollisionGroups::new(COLLIDER_GROUP_PADDLE, COLLIDER_GROUP_BALL | COLLIDER_GROUP_PICKUP))
             .insert(ActiveEvents::COLLISION_EVENTS)
             .insert(Collidable {
@@ -181,7 +183,7 @@ fn ship_articulate(mut query: Query<(&ActionState<MatchActions>, &mut Ship)>) {
         let t_up = action_state.value(MatchActions::ArticulateUp).ceil();
         let t_down = action_state.value(MatchActions::ArticulateDown).ceil() * -1.0;
 
-        let ty = (t_up + t_down) * 30.0;
+        let ty = (t_up + t_down) * LAYER_HEIGHT;
 
         let new_tp = Vec3::new(tx, PADDLE_RESTING_Y + ty, tz);
 
@@ -6,5 +6,6 @@ pub enum GameState {
     InMatch,
     PostMatch,
     NextLevel,
-    MatchResult
+    MatchResult,
+    Editor
 }
\ No newline at end of file
@@ -13,6 +13,7 @@ use crate::ui::{UIAction, UIEvents};
 enum OptionValues {
     NewGame,
     Settings,
+    Editor,
 }
 
 
@@ -23,6 +24,7 @@ impl TryFrom<u8> for OptionValues {
         match value {
             0 => Ok(Self::NewGame),
             1 => Ok(Self::Settings),
+            2 => Ok(Self::Editor),
 
             _ => Err(())
         }
@@ -142,6 +144,17 @@ fn ui_spawn(
                 .insert(SelectOptions {
                     value: OptionValues::Settings
                 });
+
+            parent.spawn(TextBundle::from_sections([
+                TextSection::new(
+                    "Level Editor", style.clone(),
+                )
+            ])
+                .with_style(centered.clone())
+            )
+                .insert(SelectOptions {
+                    value: OptionValues::Editor
+                });
         })
         .insert(UITag)
         .insert(InputManagerBundle::<UIAction> {
@@ -151,6 +164,8 @@ fn ui_spawn(
                 .insert(KeyCode::Space, UIAction::ActivateSelection)
                 .insert(GamepadButtonType::DPadDown, UIAction::SelectDown)
                 .insert(GamepadButtonType::DPadUp, UIAction::SelectUp)
+                .insert(KeyCode::Down, UIAction::SelectDown)
+                .insert(KeyCode::Up, UIAction::SelectUp)
                 .build(),
         })
     ;
@@ -225,6 +240,8 @@ fn ui_update(
                     OptionValues::NewGame => game_event.send(GameFlowEvent::StartMatch),
 
                     OptionValues::Settings => {}
+
+                    OptionValues::Editor => game_event.send(GameFlowEvent::OpenEditor),
                 }
             }
         }
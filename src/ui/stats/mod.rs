@@ -9,6 +9,7 @@ use bevy::utils::default;
 use bevy_rapier3d::prelude::Velocity;
 use crate::ball::Ball;
 use crate::config::DEBUG_INFO_ENABLED;
+use crate::level::Levels;
 
 use crate::player::Player;
 use crate::r#match::state::MatchState;
@@ -29,6 +30,8 @@ enum UIInfoTag {
     Balls,
     BlocksHit,
     BlocksLost,
+    TimeLeft,
+    Objective,
     BallsInPLay,
     BallsGrabbed,
     BallsLost,
@@ -76,6 +79,7 @@ fn ui_update_infos(
     player_stats: Query<&Player>,
     mut ui: Query<(&mut Text, &UIInfoTag), Without<Ball>>,
     balls: Query<&Velocity, With<Ball>>,
+    levels: Res<Levels>,
     diagnostics: Res<Diagnostics>,
 ) {
     let player = player_stats.get_single().unwrap();
@@ -102,6 +106,27 @@ fn ui_update_infos(
             UIInfoTag::Balls => text.sections[1].value = format!("{}", player.balls_available),
             UIInfoTag::BlocksHit => text.sections[1].value = format!("{}", match_stats.blocks_hit),
             UIInfoTag::BlocksLost => text.sections[1].value = format!("{}", match_stats.blocks_lost),
+            UIInfoTag::TimeLeft => {
+                let time_limit = levels.get_current_level().and_then(|level| level.time_limit);
+
+                text.sections[1].value = match match_stats.time_remaining(time_limit) {
+                    Some(remaining) => {
+                        // Round up, so the clock reads 0:00 only once the time is up
+                        let secs = remaining.as_secs() + if remaining.subsec_nanos() > 0 { 1 } else { 0 };
+                        format!("{}:{:02}", secs / 60, secs % 60)
+                    }
+                    None => "-".to_string(),
+                }
+            }
+            UIInfoTag::Objective => {
+                if let Some(level) = levels.get_current_level() {
+                    text.sections[1].value = format!(
+                        "{} ({:.0}%)",
+                        level.win_criteria.describe(&match_stats),
+                        100.0 * level.win_criteria.progress(&match_stats)
+                    );
+                }
+            }
             UIInfoTag::BallsInPLay => text.sections[1].value = format!("{}", player.balls_in_play),
             UIInfoTag::BallsGrabbed => text.sections[1].value = format!("{}", player.balls_grabbed),
             UIInfoTag::BallsLost => text.sections[1].value = format!("{}", player.balls_lost),
@@ -125,7 +150,12 @@ fn ui_update_infos(
 fn ui_spawn(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    levels: Res<Levels>,
 ) {
+    let has_time_limit = levels
+        .get_current_level()
+        .map_or(false, |level| level.time_limit.is_some());
+
     let style = TextStyle {
         font: asset_server.load("fonts/Orbitron-Regular.ttf"),
         font_size: 30.0,
@@ -144,6 +174,14 @@ fn ui_spawn(
         })
         .with_children(|parent| {
 
+            parent
+                .spawn(TextBundle::from_sections([
+                    TextSection::new(
+                        "Objective: ", style.clone(),
+                    ),
+                    TextSection::from_style(style.clone())
+                ])).insert(UIInfoTag::Objective);
+
             parent
                 .spawn(TextBundle::from_sections([
                     TextSection::new(
@@ -193,6 +231,16 @@ fn ui_spawn(
                     TextSection::from_style(style.clone())
                 ])).insert(UIInfoTag::BlocksLost);
 
+            if has_time_limit {
+                parent
+                    .spawn(TextBundle::from_sections([
+                        TextSection::new(
+                            "Time left: ", style.clone(),
+                        ),
+                        TextSection::from_style(style.clone())
+                    ])).insert(UIInfoTag::TimeLeft);
+            }
+
             if DEBUG_INFO_ENABLED {
 
                 parent